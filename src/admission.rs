@@ -0,0 +1,85 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::errors::DataFusionError;
+
+#[derive(Debug, Default)]
+struct State {
+    running: usize,
+    /// the tickets of the executions waiting to run, in order of arrival
+    queue: VecDeque<u64>,
+    next_ticket: u64,
+}
+
+/// Limits the number of concurrent executions of the DataFrames of a context.
+/// Executions over the limit wait in a FIFO queue, for up to `queue_timeout`.
+#[derive(Debug, Default)]
+pub(crate) struct Admission {
+    max_concurrent: Option<usize>,
+    queue_timeout: Option<Duration>,
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+/// The permission to execute, released when dropped
+pub(crate) struct Permit {
+    admission: Arc<Admission>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.admission.state.lock().unwrap().running -= 1;
+        self.admission.changed.notify_all();
+    }
+}
+
+impl Admission {
+    pub fn new(max_concurrent: Option<usize>, queue_timeout: Option<Duration>) -> Self {
+        Self {
+            max_concurrent,
+            queue_timeout,
+            ..Default::default()
+        }
+    }
+
+    /// waits for the turn of a new execution, failing if it waits more than the queue timeout
+    pub fn acquire(self: &Arc<Self>) -> Result<Permit, DataFusionError> {
+        let max_concurrent = self.max_concurrent.unwrap_or(usize::MAX);
+        let deadline = self.queue_timeout.map(|timeout| Instant::now() + timeout);
+
+        let mut state = self.state.lock().unwrap();
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.queue.push_back(ticket);
+
+        while state.queue.front() != Some(&ticket) || state.running >= max_concurrent {
+            state = match deadline {
+                None => self.changed.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        state.queue.retain(|t| *t != ticket);
+                        drop(state);
+                        // the next in the queue may be able to run
+                        self.changed.notify_all();
+                        return Err(DataFusionError::Common(format!(
+                            "The query waited more than {:?} to run ({} queries can run at a time)",
+                            self.queue_timeout.unwrap_or_default(),
+                            max_concurrent
+                        )));
+                    }
+                    self.changed.wait_timeout(state, deadline - now).unwrap().0
+                }
+            };
+        }
+        state.queue.pop_front();
+        state.running += 1;
+        drop(state);
+        self.changed.notify_all();
+
+        Ok(Permit {
+            admission: self.clone(),
+        })
+    }
+}
@@ -0,0 +1,118 @@
+use pyo3::{prelude::*, wrap_pyfunction};
+
+use arrow::record_batch::RecordBatch;
+use datafusion::datasource::MemTable;
+use datafusion::execution::context::ExecutionContext as _ExecutionContext;
+use datafusion::logical_plan::{self, col, lit, Expr, LogicalPlanBuilder};
+
+use crate::errors;
+use crate::runtime::RuntimeConfig;
+use crate::to_py::to_py_array;
+use crate::to_rust;
+
+/// executes `values` (e.g. a pyarrow Array), available as the column `value`, grouped by
+/// `group_expr` and aggregated with `aggr_expr`, returning the batches of the result
+fn execute(
+    values: MemTable,
+    group_expr: Vec<Expr>,
+    aggr_expr: Vec<Expr>,
+) -> Result<Vec<RecordBatch>, errors::DataFusionError> {
+    let mut ctx = _ExecutionContext::new();
+    ctx.register_table("values", Box::new(values));
+
+    let plan = ctx.table("values")?.to_logical_plan();
+    let plan = LogicalPlanBuilder::from(&plan)
+        .aggregate(group_expr, aggr_expr)?
+        .build()?;
+    let plan = ctx.optimize(&plan)?;
+    let plan = ctx.create_physical_plan(&plan)?;
+
+    let mut rt = RuntimeConfig::default().build()?;
+    Ok(rt.block_on(ctx.collect(plan))?)
+}
+
+/// aggregates `values` (see `execute`), returning each column of the result as a pyarrow Array
+fn aggregate(
+    values: &PyAny,
+    group_expr: Vec<Expr>,
+    aggr_expr: Vec<Expr>,
+    py: Python,
+) -> PyResult<Vec<PyObject>> {
+    let table = to_rust::to_rust_values_table(values, py)?;
+    let columns = group_expr.len() + aggr_expr.len();
+    let batches = py.allow_threads(move || execute(table, group_expr, aggr_expr))?;
+
+    let concat_arrays = py.import("pyarrow")?.getattr("concat_arrays")?;
+    (0..columns)
+        .map(|i| {
+            let arrays = batches
+                .iter()
+                .map(|batch| to_py_array(batch.column(i), py))
+                .collect::<PyResult<Vec<_>>>()?;
+            Ok(concat_arrays.call1((arrays,))?.to_object(py))
+        })
+        .collect()
+}
+
+/// aggregates `values` with `expr`, returning the result as a pyarrow Scalar
+fn aggregate_scalar(values: &PyAny, expr: Expr, py: Python) -> PyResult<PyObject> {
+    let columns = aggregate(values, vec![], vec![expr], py)?;
+    Ok(columns[0].call_method1(py, "__getitem__", (0,))?)
+}
+
+/// Returns the sum of the values of an array (e.g. a column of a result)
+#[pyfunction]
+fn sum(values: &PyAny, py: Python) -> PyResult<PyObject> {
+    aggregate_scalar(values, logical_plan::sum(col("value")), py)
+}
+
+/// Returns the minimum of the values of an array (e.g. a column of a result)
+#[pyfunction]
+fn min(values: &PyAny, py: Python) -> PyResult<PyObject> {
+    aggregate_scalar(values, logical_plan::min(col("value")), py)
+}
+
+/// Returns the maximum of the values of an array (e.g. a column of a result)
+#[pyfunction]
+fn max(values: &PyAny, py: Python) -> PyResult<PyObject> {
+    aggregate_scalar(values, logical_plan::max(col("value")), py)
+}
+
+/// Returns the distinct values of an array (e.g. a column of a result), in no particular order
+#[pyfunction]
+fn unique(values: &PyAny, py: Python) -> PyResult<PyObject> {
+    let columns = aggregate(
+        values,
+        vec![col("value")],
+        vec![logical_plan::count(col("value"))],
+        py,
+    )?;
+    Ok(columns[0].clone())
+}
+
+/// Returns the distinct values of an array (e.g. a column of a result) with the number of times
+/// each occurs, as a pyarrow StructArray with the fields `values` and `counts`, in no particular
+/// order.
+#[pyfunction]
+fn value_counts(values: &PyAny, py: Python) -> PyResult<PyObject> {
+    let columns = aggregate(
+        values,
+        vec![col("value")],
+        vec![logical_plan::count(lit(1))],
+        py,
+    )?;
+    let result = py
+        .import("pyarrow")?
+        .getattr("StructArray")?
+        .call_method1("from_arrays", (columns, vec!["values", "counts"]))?;
+    Ok(result.to_object(py))
+}
+
+pub fn init(module: &PyModule) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(sum, module)?)?;
+    module.add_function(wrap_pyfunction!(min, module)?)?;
+    module.add_function(wrap_pyfunction!(max, module)?)?;
+    module.add_function(wrap_pyfunction!(unique, module)?)?;
+    module.add_function(wrap_pyfunction!(value_counts, module)?)?;
+    Ok(())
+}
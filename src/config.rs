@@ -0,0 +1,70 @@
+use pyo3::prelude::*;
+
+use datafusion::execution::context::ExecutionConfig as _ExecutionConfig;
+
+use crate::errors;
+
+/// Settings of the plans of an `ExecutionContext`: the number of partitions that they are
+/// executed in (`concurrency`, by default the number of cores) and the number of rows of the
+/// batches that they read (`batch_size`). Unset values use DataFusion's defaults.
+/// With `ordered_aggregates`, the results of SQL statements with a `GROUP BY` and without an
+/// `ORDER BY` are sorted by all their columns, e.g. for tests comparing results to snapshots.
+/// There is no `default_catalog`: DataFusion has no catalogs yet, tables are registered in a
+/// single namespace per context.
+#[pyclass]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ExecutionConfig {
+    #[pyo3(get)]
+    concurrency: Option<usize>,
+    #[pyo3(get)]
+    batch_size: Option<usize>,
+    #[pyo3(get)]
+    pub ordered_aggregates: bool,
+}
+
+#[pymethods]
+impl ExecutionConfig {
+    #[new]
+    #[args(
+        concurrency = "None",
+        batch_size = "None",
+        ordered_aggregates = "false"
+    )]
+    fn new(
+        concurrency: Option<usize>,
+        batch_size: Option<usize>,
+        ordered_aggregates: bool,
+    ) -> PyResult<Self> {
+        if concurrency == Some(0) {
+            return Err(errors::DataFusionError::Common(
+                "concurrency must be at least 1".to_owned(),
+            )
+            .into());
+        }
+        if batch_size == Some(0) {
+            return Err(errors::DataFusionError::Common(
+                "batch_size must be at least 1".to_owned(),
+            )
+            .into());
+        }
+        Ok(Self {
+            concurrency,
+            batch_size,
+            ordered_aggregates,
+        })
+    }
+}
+
+impl ExecutionConfig {
+    /// DataFusion's configuration with these settings
+    pub fn to_config(&self) -> _ExecutionConfig {
+        let mut config = _ExecutionConfig::new();
+        if let Some(concurrency) = self.concurrency {
+            config = config.with_concurrency(concurrency);
+        }
+        if let Some(batch_size) = self.batch_size {
+            config = config.with_batch_size(batch_size);
+        }
+        config
+    }
+}
@@ -1,19 +1,46 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
 
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 
-use pyo3::prelude::*;
+use pyo3::{
+    prelude::*,
+    types::{IntoPyDict, PyDict, PyList},
+};
 
+use arrow::datatypes::SchemaRef;
 use arrow::record_batch::RecordBatch;
-use datafusion::datasource::MemTable;
-use datafusion::execution::context::ExecutionContext as _ExecutionContext;
+use datafusion::datasource::{MemTable, TableProvider};
+use datafusion::execution::context::{
+    ExecutionContext as _ExecutionContext, ExecutionContextState,
+};
+use datafusion::logical_plan::{col, Expr, LogicalPlan, LogicalPlanBuilder};
+use datafusion::optimizer::utils;
+use datafusion::scalar::ScalarValue;
 
+use crate::admission::Admission;
+use crate::config::ExecutionConfig;
 use crate::dataframe;
+use crate::datasource::{self, TableSource};
+use crate::dbapi::DbApiTable;
 use crate::errors;
+use crate::expression::Expression;
 use crate::functions;
+use crate::metrics::Metrics;
+use crate::policy::SqlPolicy;
+use crate::provider::PyTableProvider;
+use crate::runtime::RuntimeConfig;
+use crate::scalar;
+use crate::stream;
+use crate::suggestions;
 use crate::to_rust;
 use crate::types::PyDataType;
+use crate::unparser;
+use crate::variables;
+use crate::view::{SharedTable, ViewDefinition};
+use crate::writer;
 
 /// `ExecutionContext` is able to plan and execute DataFusion plans.
 /// It has a powerful optimizer, a physical planner for local execution, and a
@@ -21,27 +48,230 @@ use crate::types::PyDataType;
 #[pyclass(unsendable)]
 pub(crate) struct ExecutionContext {
     ctx: _ExecutionContext,
+    runtime: RuntimeConfig,
+    metrics: Arc<Metrics>,
+    admission: Arc<Admission>,
+    /// the sources of the tables registered from files
+    sources: HashMap<String, TableSource>,
+    /// the views applied to a table on registration, in order, by table name
+    views: HashMap<String, Vec<ViewDefinition>>,
+    /// the names of the tables registered with `register_temp_table`
+    temp_tables: HashSet<String>,
+    /// the values of the variables of SQL statements, by name (without `@`)
+    variables: HashMap<String, ScalarValue>,
+    /// whether the results of aggregations are sorted, see `ExecutionConfig`
+    ordered_aggregates: bool,
 }
 
 #[pymethods]
 impl ExecutionContext {
+    /// `worker_threads` is the number of threads executing plans and `max_threads` bounds the
+    /// total number of threads, including those used for blocking IO.
+    /// `max_concurrent_queries` limits the number of DataFrames of this context executing at the
+    /// same time; the others wait for their turn (in order of arrival), failing if they wait
+    /// more than `queue_timeout` seconds.
+    /// With `target_batch_bytes`, tables are read in batches of about that many bytes, estimated
+    /// from the columns read, instead of a fixed number of rows.
+    /// `config` (an `ExecutionConfig`) sets the concurrency and batch size of plans.
     #[new]
-    fn new() -> Self {
-        ExecutionContext {
-            ctx: _ExecutionContext::new(),
+    #[args(
+        worker_threads = "None",
+        max_threads = "None",
+        max_concurrent_queries = "None",
+        queue_timeout = "None",
+        target_batch_bytes = "None",
+        config = "None"
+    )]
+    fn new(
+        worker_threads: Option<usize>,
+        max_threads: Option<usize>,
+        max_concurrent_queries: Option<usize>,
+        queue_timeout: Option<f64>,
+        target_batch_bytes: Option<usize>,
+        config: Option<ExecutionConfig>,
+    ) -> PyResult<Self> {
+        if let (Some(worker_threads), Some(max_threads)) = (worker_threads, max_threads) {
+            if max_threads < worker_threads {
+                return Err(errors::DataFusionError::Common(format!(
+                    "max_threads ({}) must be at least worker_threads ({})",
+                    max_threads, worker_threads
+                ))
+                .into());
+            }
         }
+        if target_batch_bytes == Some(0) {
+            return Err(errors::DataFusionError::Common(
+                "target_batch_bytes must be at least 1".to_owned(),
+            )
+            .into());
+        }
+        if max_concurrent_queries == Some(0) {
+            return Err(errors::DataFusionError::Common(
+                "max_concurrent_queries must be at least 1".to_owned(),
+            )
+            .into());
+        }
+        let queue_timeout = match queue_timeout {
+            Some(seconds) if !(seconds >= 0.0 && seconds.is_finite()) => {
+                return Err(errors::DataFusionError::Common(format!(
+                    "queue_timeout must be a non-negative number of seconds, got {}",
+                    seconds
+                ))
+                .into())
+            }
+            Some(seconds) => Some(Duration::from_secs_f64(seconds)),
+            None => None,
+        };
+        let config = config.unwrap_or_default();
+        Ok(ExecutionContext {
+            ctx: _ExecutionContext::with_config(config.to_config()),
+            runtime: RuntimeConfig {
+                worker_threads,
+                max_threads,
+                target_batch_bytes,
+            },
+            metrics: Arc::new(Metrics::default()),
+            admission: Arc::new(Admission::new(max_concurrent_queries, queue_timeout)),
+            sources: HashMap::new(),
+            views: HashMap::new(),
+            temp_tables: HashSet::new(),
+            variables: HashMap::new(),
+            ordered_aggregates: config.ordered_aggregates,
+        })
     }
 
     /// Returns a DataFrame whose plan corresponds to the SQL statement.
-    fn sql(&mut self, query: &str) -> PyResult<dataframe::DataFrame> {
-        let df = self
-            .ctx
-            .sql(query)
-            .map_err(|e| -> errors::DataFusionError { e.into() })?;
-        Ok(dataframe::DataFrame::new(
-            self.ctx.state.clone(),
-            df.to_logical_plan(),
-        ))
+    /// `tables` optionally maps names to sequences (e.g. lists or pyarrow arrays) that are
+    /// available to this statement only, as tables with a single column named `value`.
+    /// With `max_rows`, results have at most `max_rows` rows, e.g. to run untrusted queries;
+    /// whether a result was truncated is reported by `DataFrame.collect_with_stats`.
+    /// With `policy` (a `SqlPolicy`), the statement fails with a `PermissionError` when it
+    /// references a table or function that the policy doesn't allow; `tables` are always allowed.
+    /// Variables (see `set_variable`) are replaced by their values.
+    /// With `sample`, the result is approximated from a random fraction of the rows of the
+    /// tables, see `DataFrame.approx`.
+    #[args(tables = "None", max_rows = "None", policy = "None", sample = "None")]
+    fn sql(
+        &mut self,
+        query: &str,
+        tables: Option<&PyDict>,
+        max_rows: Option<usize>,
+        policy: Option<SqlPolicy>,
+        sample: Option<f64>,
+        py: Python,
+    ) -> PyResult<dataframe::DataFrame> {
+        let query = &variables::substitute(query, &self.variables)?;
+        // every table is converted before any is registered, so that none is left registered
+        // when one of them fails
+        let mut temporary = vec![];
+        if let Some(tables) = tables {
+            for (name, values) in tables.iter() {
+                let name = name.extract::<String>()?;
+                if self.ctx.tables().contains(&name) {
+                    return Err(errors::DataFusionError::Common(format!(
+                        "The table \"{}\" is already registered",
+                        name
+                    ))
+                    .into());
+                }
+                temporary.push((name, to_rust::to_rust_values_table(values, py)?));
+            }
+        }
+        let mut names = vec![];
+        for (name, table) in temporary {
+            self.ctx.register_table(&name, Box::new(table));
+            names.push(name);
+        }
+
+        let plan = self.plan_sql(query, policy.as_ref(), &names, py);
+
+        // the DataFrame keeps its own copy of the state: the temporary tables are only
+        // removed from the context
+        let state = self.ctx.state.clone();
+        for name in names {
+            self.ctx.state.datasources.remove(&name);
+        }
+        let df = dataframe::DataFrame::new(
+            state,
+            plan?,
+            self.runtime,
+            self.metrics.clone(),
+            self.admission.clone(),
+        )
+        .with_max_rows(max_rows);
+        match sample {
+            Some(fraction) => df.approx(fraction),
+            None => Ok(df),
+        }
+    }
+
+    /// Executes the SQL statement lazily, returning an iterator of `RecordBatch`es, e.g. to
+    /// consume results that don't fit in memory. See `DataFrame.iter_batches`.
+    #[args(batch_size = "None")]
+    fn sql_iter(
+        &mut self,
+        query: &str,
+        batch_size: Option<usize>,
+        py: Python,
+    ) -> PyResult<stream::RecordBatchStream> {
+        self.sql(query, None, None, None, None, py)?
+            .iter_batches(batch_size, py)
+    }
+
+    /// Returns a description of the plans of the SQL statement, see `DataFrame.explain`.
+    #[args(verbose = "false")]
+    fn explain(&mut self, query: &str, verbose: bool, py: Python) -> PyResult<String> {
+        self.sql(query, None, None, None, None, py)?
+            .explain("text", verbose)
+    }
+
+    /// Executes `query_or_df` (a SQL statement or a DataFrame), writing its result to `path` as a
+    /// parquet file. Batches are written as they are computed, without converting them to
+    /// Python, so that results larger than memory can be written. `path` only appears once
+    /// completely written. Returns the number of rows written.
+    /// `mode` is what to do when `path` exists: `"overwrite"` it or `"error_if_exists"`.
+    /// With `verify`, the number of rows in the footer of the written file is checked (its data
+    /// pages are not decoded) and a manifest is returned instead, see `DataFrame.write_parquet`.
+    #[args(verify = "false", mode = "\"overwrite\"")]
+    fn write_parquet(
+        &mut self,
+        query_or_df: &PyAny,
+        path: &str,
+        verify: bool,
+        mode: &str,
+        py: Python,
+    ) -> PyResult<PyObject> {
+        writer::WriteMode::parse(mode)?.check_file(path)?;
+        let mut stream = self.stream_of(query_or_df, py)?;
+        let schema = stream.schema();
+        let rows = py.allow_threads(|| writer::write_parquet_stream(path, schema, &mut stream))?;
+        if verify {
+            let file = writer::verify_parquet_file(path, rows)?;
+            return writer::manifest(&[file], py);
+        }
+        Ok(rows.to_object(py))
+    }
+
+    /// Executes `query_or_df` like `write_parquet`, writing its result to `path` as a CSV file
+    /// with a header.
+    #[args(verify = "false", mode = "\"overwrite\"")]
+    fn write_csv(
+        &mut self,
+        query_or_df: &PyAny,
+        path: &str,
+        verify: bool,
+        mode: &str,
+        py: Python,
+    ) -> PyResult<PyObject> {
+        writer::WriteMode::parse(mode)?.check_file(path)?;
+        let mut stream = self.stream_of(query_or_df, py)?;
+        let schema = stream.schema();
+        let rows = py.allow_threads(|| writer::write_csv_stream(path, &mut stream))?;
+        if verify {
+            let file = writer::verify_csv_file(path, schema, rows)?;
+            return writer::manifest(&[file], py);
+        }
+        Ok(rows.to_object(py))
     }
 
     fn create_dataframe(
@@ -49,33 +279,256 @@ impl ExecutionContext {
         partitions: Vec<Vec<PyObject>>,
         py: Python,
     ) -> PyResult<dataframe::DataFrame> {
-        let partitions: Vec<Vec<RecordBatch>> = partitions
-            .iter()
-            .map(|batches| {
-                batches
-                    .iter()
-                    .map(|batch| to_rust::to_rust_batch(batch.as_ref(py)))
-                    .collect()
-            })
+        let partitions = to_rust_partitions(partitions, py)?;
+        self.create_dataframe_from_batches(partitions)
+    }
+
+    /// Returns a DataFrame from a dictionary mapping column names to sequences of values.
+    /// The schema is inferred from the values.
+    fn from_pydict(&mut self, data: &PyDict, py: Python) -> PyResult<dataframe::DataFrame> {
+        let batches = pydict_to_batches(data, py)?;
+        self.create_dataframe_from_batches(vec![batches])
+    }
+
+    /// Returns a DataFrame from a list of rows, each a dictionary mapping column names to values.
+    /// Columns are ordered by first appearance and missing values are null.
+    fn from_pylist(&mut self, data: Vec<&PyDict>, py: Python) -> PyResult<dataframe::DataFrame> {
+        let columns = PyDict::new(py);
+        for (i, row) in data.iter().enumerate() {
+            for (name, value) in row.iter() {
+                if columns.get_item(name).is_none() {
+                    columns.set_item(name, vec![py.None(); data.len()])?;
+                }
+                let column = columns.get_item(name).unwrap().downcast::<PyList>()?;
+                column.set_item(i as isize, value)?;
+            }
+        }
+        self.from_pydict(columns, py)
+    }
+
+    /// Returns a DataFrame that scans a parquet file or a directory of parquet files.
+    fn read_parquet(&mut self, path: &str) -> PyResult<dataframe::DataFrame> {
+        let table = datasource::parquet_table(path)?;
+        self.create_dataframe_from_table(Box::new(table))
+    }
+
+    /// Returns a DataFrame that scans a CSV file or a directory of CSV files.
+    /// `schema` is an optional list of `(name, pyarrow type)`; when absent, it is inferred.
+    #[args(schema = "None", has_header = "true", delimiter = "\",\"")]
+    fn read_csv(
+        &mut self,
+        path: &str,
+        schema: Option<Vec<(String, PyDataType)>>,
+        has_header: bool,
+        delimiter: &str,
+    ) -> PyResult<dataframe::DataFrame> {
+        let table = datasource::csv_table(path, schema, has_header, delimiter)?;
+        self.create_dataframe_from_table(Box::new(table))
+    }
+
+    /// Returns a DataFrame with the contents of a newline-delimited JSON file, which is read
+    /// into memory. `schema` is an optional list of `(name, pyarrow type)`; when absent, it is
+    /// inferred from the first `schema_infer_max_records` records.
+    #[args(schema = "None", schema_infer_max_records = "1000")]
+    fn read_json(
+        &mut self,
+        path: &str,
+        schema: Option<Vec<(String, PyDataType)>>,
+        schema_infer_max_records: usize,
+    ) -> PyResult<dataframe::DataFrame> {
+        let batch_size = self.ctx.state.config.batch_size;
+        let table = datasource::json_table(path, schema, schema_infer_max_records, batch_size)?;
+        self.create_dataframe_from_table(Box::new(table))
+    }
+
+    /// Registers a parquet file or a directory of parquet files as a table.
+    /// Directories are listed on every execution, so new files are picked up by new queries.
+    /// When `columns` is given, the table only has these columns and only they are read.
+    #[args(columns = "None")]
+    fn register_parquet(
+        &mut self,
+        name: &str,
+        path: &str,
+        columns: Option<Vec<String>>,
+    ) -> PyResult<()> {
+        let source = TableSource::Parquet {
+            path: path.to_owned(),
+            columns,
+        };
+        self.register_table_from(name, source.to_table()?, Some(source))?;
+        Ok(())
+    }
+
+    /// Registers a CSV file or a directory of CSV files as a table.
+    /// `schema` is an optional list of `(name, pyarrow type)`; when absent, it is inferred.
+    #[args(schema = "None", has_header = "true", delimiter = "\",\"")]
+    fn register_csv(
+        &mut self,
+        name: &str,
+        path: &str,
+        schema: Option<Vec<(String, PyDataType)>>,
+        has_header: bool,
+        delimiter: &str,
+    ) -> PyResult<()> {
+        let source = TableSource::Csv {
+            path: path.to_owned(),
+            schema,
+            has_header,
+            delimiter: delimiter.to_owned(),
+        };
+        self.register_table_from(name, source.to_table()?, Some(source))?;
+        Ok(())
+    }
+
+    /// Registers a newline-delimited JSON file as a table, which is read into memory (again when
+    /// refreshed). `schema` and `schema_infer_max_records` are as on `read_json`.
+    #[args(schema = "None", schema_infer_max_records = "1000")]
+    fn register_json(
+        &mut self,
+        name: &str,
+        path: &str,
+        schema: Option<Vec<(String, PyDataType)>>,
+        schema_infer_max_records: usize,
+    ) -> PyResult<()> {
+        let source = TableSource::Json {
+            path: path.to_owned(),
+            schema,
+            schema_infer_max_records,
+            batch_size: self.ctx.state.config.batch_size,
+        };
+        self.register_table_from(name, source.to_table()?, Some(source))?;
+        Ok(())
+    }
+
+    /// Registers `partitions`, a list of lists of pyarrow RecordBatches, as an in-memory table.
+    fn register_record_batches(
+        &mut self,
+        name: &str,
+        partitions: Vec<Vec<PyObject>>,
+        py: Python,
+    ) -> PyResult<()> {
+        let partitions = to_rust_partitions(partitions, py)?;
+        let table = memory_table(partitions)?;
+        self.register_table_from(name, Box::new(table), None)?;
+        Ok(())
+    }
+
+    /// Registers a temporary table `name`, e.g. a scratch table of a notebook, with the result of
+    /// `data`: a DataFrame (which is executed) or a list of pyarrow RecordBatches.
+    /// Temporary tables are listed by `temp_tables` and, like other tables, dropped by `reset`;
+    /// registering another table under the same name makes it permanent.
+    fn register_temp_table(&mut self, name: &str, data: &PyAny, py: Python) -> PyResult<()> {
+        let table = match data.extract::<PyRef<dataframe::DataFrame>>() {
+            Ok(df) => {
+                let (schema, partitions, _) = df.execute_partitions(true, py)?;
+                errors::wrap(MemTable::new(schema, partitions))?
+            }
+            Err(_) => {
+                let batches = data.extract::<Vec<PyObject>>()?;
+                memory_table(to_rust_partitions(vec![batches], py)?)?
+            }
+        };
+        self.register_table_from(name, Box::new(table), None)?;
+        self.temp_tables.insert(name.to_owned());
+        Ok(())
+    }
+
+    /// Registers a `pyarrow.Table` as an in-memory table. Its buffers are shared, not copied.
+    fn register_arrow(&mut self, name: &str, table: &PyAny) -> PyResult<()> {
+        let batches = table
+            .call_method0("to_batches")?
+            .iter()?
+            .map(|batch| to_rust::to_rust_batch(batch?))
             .collect::<PyResult<_>>()?;
+        let table = memory_table(vec![batches])?;
+        self.register_table_from(name, Box::new(table), None)?;
+        Ok(())
+    }
 
-        let table = errors::wrap(MemTable::new(partitions[0][0].schema(), partitions))?;
+    /// Registers a `pandas.DataFrame` as an in-memory table, converted to Arrow (without its
+    /// index) by pyarrow.
+    fn register_pandas(&mut self, name: &str, df: &PyAny, py: Python) -> PyResult<()> {
+        let kwargs = [("preserve_index", false)].into_py_dict(py);
+        let table = py.import("pyarrow")?.getattr("Table")?.call_method(
+            "from_pandas",
+            (df,),
+            Some(kwargs),
+        )?;
+        self.register_arrow(name, table)
+    }
 
-        // generate a random (unique) name for this table
-        let name = rand::thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(10)
-            .collect::<String>();
+    /// Registers a dictionary mapping column names to numpy arrays (or any sequence of values) as
+    /// an in-memory table.
+    fn register_numpy(&mut self, name: &str, data: &PyDict, py: Python) -> PyResult<()> {
+        let batches = pydict_to_batches(data, py)?;
+        let table = memory_table(vec![batches])?;
+        self.register_table_from(name, Box::new(table), None)?;
+        Ok(())
+    }
 
-        self.ctx.register_table(&name, Box::new(table));
-        Ok(dataframe::DataFrame::new(
-            self.ctx.state.clone(),
-            errors::wrap(self.ctx.table(&name))?.to_logical_plan(),
-        ))
+    /// Registers every parquet and CSV dataset under the directory `path` as a table named
+    /// after its relative path (e.g. `sales/2020.csv` is registered as `sales_2020`).
+    /// A directory whose files are all of a single format is a single dataset.
+    /// Returns the names of the registered tables.
+    fn register_directory(&mut self, path: &str) -> PyResult<Vec<String>> {
+        let datasets = datasource::discover(path)?;
+        let mut names = vec![];
+        for (name, source) in datasets {
+            self.register_table_from(&name, source.to_table()?, Some(source))?;
+            names.push(name);
+        }
+        Ok(names)
+    }
+
+    /// Re-creates the table `name` from the files it was registered from, e.g. to pick up a
+    /// schema change. DataFrames created before the refresh keep using the previous table.
+    fn refresh_table(&mut self, name: &str) -> PyResult<()> {
+        if !self.ctx.state.datasources.contains_key(name) {
+            return Err(errors::DataFusionError::TableNotFound(name.to_owned()).into());
+        }
+        let source = match self.sources.get(name) {
+            Some(source) => source.clone(),
+            None => {
+                return Err(errors::DataFusionError::Common(format!(
+                    "The table \"{}\" was not registered from files and can't be refreshed",
+                    name
+                ))
+                .into())
+            }
+        };
+        self.register_table_from(name, source.to_table()?, Some(source))?;
+        Ok(())
+    }
+
+    /// Registers the result of `query` on a Python DB-API 2.0 `connection` (e.g. `sqlite3`) as a
+    /// table. The query runs on every execution. `schema` is an optional list of
+    /// `(name, pyarrow type)`; when absent, it is inferred from the first rows of the query.
+    /// Only the columns used by the statement are selected, but filters are not pushed down to
+    /// the database yet: every execution fetches all the rows of the query.
+    #[args(schema = "None")]
+    fn register_dbapi(
+        &mut self,
+        name: &str,
+        connection: PyObject,
+        query: &str,
+        schema: Option<Vec<(String, PyDataType)>>,
+        py: Python,
+    ) -> PyResult<()> {
+        let schema = schema.map(datasource::to_schema);
+        let table = DbApiTable::try_new(connection, query, schema, py)?;
+        self.register_table_from(name, Box::new(table), None)?;
+        Ok(())
     }
 
-    fn register_parquet(&mut self, name: &str, path: &str) -> PyResult<()> {
-        errors::wrap(self.ctx.register_parquet(name, path))?;
+    /// Registers a table `name` whose rows are provided by `provider`, a Python object with the
+    /// methods `schema()`, returning a pyarrow Schema, and `scan(projection, filters)`, returning
+    /// an iterable of pyarrow RecordBatches, e.g. to query a REST API or generated data with SQL.
+    /// `projection` is the list of the columns used by the statement (`None` for all of them);
+    /// `filters` is always empty for now, filters being applied to the scanned rows.
+    /// `scan` is called on every execution.
+    fn register_provider(&mut self, name: &str, provider: PyObject, py: Python) -> PyResult<()> {
+        let table = PyTableProvider::try_new(provider, py)?;
+        self.register_table_from(name, Box::new(table), None)?;
         Ok(())
     }
 
@@ -85,13 +538,449 @@ impl ExecutionContext {
         func: PyObject,
         args_types: Vec<PyDataType>,
         return_type: PyDataType,
+        py: Python,
     ) -> PyResult<()> {
+        // fails early if `func` is not callable
+        functions::callable_name(&func, py)?;
         let function = functions::create_udf(func, args_types, return_type, name)?;
 
         Ok(self.ctx.register_udf(function.function))
     }
 
+    /// Registers an aggregate UDF named `name`, usable in SQL (e.g. with `GROUP BY`).
+    /// `accumulator` is a class (or any callable) returning a new accumulator, see `udaf`.
+    fn register_udaf(
+        &mut self,
+        name: &str,
+        accumulator: PyObject,
+        input_type: PyDataType,
+        return_type: PyDataType,
+        state_type: Vec<PyDataType>,
+        py: Python,
+    ) -> PyResult<()> {
+        // fails early if `accumulator` is not callable
+        functions::callable_name(&accumulator, py)?;
+        let function =
+            functions::create_udaf(accumulator, input_type, return_type, state_type, name)?;
+
+        Ok(self.ctx.register_udaf(function.function))
+    }
+
+    /// Registers a numpy universal function (e.g. `numpy.sqrt`) as a UDF named `name`.
+    #[args(input_types = "None", return_type = "None")]
+    fn register_ufunc(
+        &mut self,
+        name: &str,
+        ufunc: PyObject,
+        input_types: Option<Vec<PyDataType>>,
+        return_type: Option<PyDataType>,
+        py: Python,
+    ) -> PyResult<()> {
+        let function = functions::create_ufunc(ufunc, input_types, return_type, name, py)?;
+
+        Ok(self.ctx.register_udf(function.function))
+    }
+
+    /// Restricts the rows of the table `name` to those for which `predicate` is true, e.g.
+    /// `col("tenant_id") == lit(42)`. The filter is part of the table: every query, DataFrame
+    /// and snapshot created afterwards only sees these rows, including after the table is
+    /// refreshed or registered again under the same name. Filters can't be removed (only
+    /// `reset` drops them): setting another one requires rows to pass both.
+    fn set_row_filter(&mut self, name: &str, predicate: Expression) -> PyResult<()> {
+        self.add_view(
+            name,
+            ViewDefinition {
+                filter: Some(predicate.expr),
+                columns: None,
+            },
+        )
+    }
+
+    /// Replaces the columns of the table `name` by `columns`, expressions over its current
+    /// columns. E.g. `[col("name").alias("customer"), mask(col("email")).alias("email")]`
+    /// renames `name` and masks `email` with the UDF `mask`. Like row filters, this is part of
+    /// the table: the previous columns are no longer reachable, including after the table is
+    /// refreshed or registered again under the same name.
+    fn set_table_columns(&mut self, name: &str, columns: Vec<Expression>) -> PyResult<()> {
+        self.add_view(
+            name,
+            ViewDefinition {
+                filter: None,
+                columns: Some(columns.into_iter().map(|e| e.expr).collect()),
+            },
+        )
+    }
+
+    /// Sets the variable `name`, referenced as `@name` in SQL statements, e.g. to parameterize a
+    /// statement without formatting it: `ctx.set_variable("cutoff", 10)` and
+    /// `ctx.sql("SELECT * FROM t WHERE a > @cutoff")`.
+    /// `value` is a bool, an int, a float, a str or a pyarrow Scalar that can be written as a SQL
+    /// literal: other values (e.g. NaN or dates, which SQL statements can't express as literals
+    /// yet) are rejected.
+    fn set_variable(&mut self, name: &str, value: &PyAny) -> PyResult<()> {
+        let value = scalar::to_scalar(value)?;
+        unparser::scalar_to_sql(&value)?;
+        self.variables
+            .insert(name.trim_start_matches('@').to_owned(), value);
+        Ok(())
+    }
+
+    /// Returns a DataFrame that scans the registered table `name`.
+    fn table(&self, name: &str) -> PyResult<dataframe::DataFrame> {
+        if !self.ctx.state.datasources.contains_key(name) {
+            return Err(errors::DataFusionError::TableNotFound(name.to_owned()).into());
+        }
+        Ok(dataframe::DataFrame::new(
+            self.ctx.state.clone(),
+            errors::wrap(self.ctx.table(name))?.to_logical_plan(),
+            self.runtime,
+            self.metrics.clone(),
+            self.admission.clone(),
+        ))
+    }
+
     fn tables(&self) -> HashSet<String> {
         self.ctx.tables()
     }
+
+    /// Returns the schema of the registered table `name`, as a list with the `name`, `type` (e.g.
+    /// `"Float64"`) and `nullable` of each of its columns. Unlike results, the schemas of tables
+    /// with types that can't be converted to Python can be described.
+    fn schema(&self, name: &str, py: Python) -> PyResult<Vec<PyObject>> {
+        self.table_schema(name)?
+            .fields()
+            .iter()
+            .map(|field| {
+                let dict = PyDict::new(py);
+                dict.set_item("name", field.name())?;
+                dict.set_item("type", format!("{:?}", field.data_type()))?;
+                dict.set_item("nullable", field.is_nullable())?;
+                Ok(dict.to_object(py))
+            })
+            .collect()
+    }
+
+    /// Returns a description of the columns of the registered table `name`, one per line, e.g.
+    /// `a: Float64` or `b: Utf8 NOT NULL`.
+    fn describe(&self, name: &str) -> PyResult<String> {
+        Ok(self
+            .table_schema(name)?
+            .fields()
+            .iter()
+            .map(|field| {
+                let null = if field.is_nullable() { "" } else { " NOT NULL" };
+                format!("{}: {:?}{}", field.name(), field.data_type(), null)
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    /// Returns the names of the tables registered with `register_temp_table`.
+    fn temp_tables(&self) -> HashSet<String> {
+        self.temp_tables.clone()
+    }
+
+    /// Drops every registered table, function and variable, keeping the configuration.
+    /// DataFrames created from this context remain valid, as they hold their own reference to
+    /// the tables they use.
+    fn reset(&mut self) {
+        self.ctx = _ExecutionContext::with_config(self.ctx.state.config.clone());
+        self.sources.clear();
+        self.views.clear();
+        self.temp_tables.clear();
+        self.variables.clear();
+    }
+
+    /// Returns a read-only view of the registered tables and functions, which can be shared
+    /// across threads. Tables registered after the snapshot are not visible to it.
+    fn snapshot(&self) -> ContextSnapshot {
+        ContextSnapshot {
+            state: self.ctx.state.clone(),
+            variables: self.variables.clone(),
+            ordered_aggregates: self.ordered_aggregates,
+            runtime: self.runtime,
+            metrics: self.metrics.clone(),
+            admission: self.admission.clone(),
+        }
+    }
+
+    /// Returns the cumulative metrics of the executions of DataFrames created by this context
+    /// (and its snapshots), in Prometheus' text exposition format: the number of queries
+    /// (failed and cancelled ones too), of output rows and batches and the execution time.
+    /// Rows scanned, bytes read and spilled bytes are not exported: DataFusion doesn't measure
+    /// its scans and never spills to disk.
+    fn metrics_prometheus(&self) -> String {
+        self.metrics.to_prometheus()
+    }
+
+    fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    /// Releases every registered table and function (see `reset`).
+    fn __exit__(
+        &mut self,
+        _exc_type: PyObject,
+        _exc_value: PyObject,
+        _traceback: PyObject,
+    ) -> bool {
+        self.reset();
+        false
+    }
+}
+
+/// An immutable view of the tables and functions of an `ExecutionContext`.
+/// It can plan queries against them but can't register or drop tables.
+#[pyclass]
+pub(crate) struct ContextSnapshot {
+    state: ExecutionContextState,
+    variables: HashMap<String, ScalarValue>,
+    ordered_aggregates: bool,
+    runtime: RuntimeConfig,
+    metrics: Arc<Metrics>,
+    admission: Arc<Admission>,
+}
+
+#[pymethods]
+impl ContextSnapshot {
+    /// Returns a DataFrame whose plan corresponds to the SQL statement.
+    /// `max_rows` and `policy` restrict the statement as on `ExecutionContext.sql`.
+    #[args(max_rows = "None", policy = "None")]
+    fn sql(
+        &self,
+        query: &str,
+        max_rows: Option<usize>,
+        policy: Option<SqlPolicy>,
+    ) -> PyResult<dataframe::DataFrame> {
+        let query = &variables::substitute(query, &self.variables)?;
+        let ctx = _ExecutionContext::from(self.state.clone());
+        let plan = ctx
+            .create_logical_plan(query)
+            .map_err(|e| suggestions::with_suggestions(e, query, &self.state))?;
+        if let LogicalPlan::CreateExternalTable { .. } = plan {
+            return Err(errors::DataFusionError::Common(
+                "Tables can't be registered on a read-only context".to_owned(),
+            )
+            .into());
+        }
+        if let Some(policy) = policy {
+            policy.check(&plan, &[])?;
+        }
+        let plan = if self.ordered_aggregates {
+            order_aggregates(plan)?
+        } else {
+            plan
+        };
+        Ok(dataframe::DataFrame::new(
+            self.state.clone(),
+            plan,
+            self.runtime,
+            self.metrics.clone(),
+            self.admission.clone(),
+        )
+        .with_max_rows(max_rows))
+    }
+
+    fn tables(&self) -> HashSet<String> {
+        self.state.datasources.keys().cloned().collect()
+    }
+}
+
+impl ExecutionContext {
+    /// registers `table` as `name`, remembering its `source` (if any) so that it can be refreshed.
+    /// The views of `name`, if any, are applied to `table`.
+    fn register_table_from(
+        &mut self,
+        name: &str,
+        table: Box<dyn TableProvider + Send + Sync>,
+        source: Option<TableSource>,
+    ) -> PyResult<()> {
+        let mut table = table;
+        for view in self.views.get(name).into_iter().flatten() {
+            table = Box::new(errors::wrap(view.apply(table.into()))?);
+        }
+        self.ctx.register_table(name, table);
+        self.temp_tables.remove(name);
+        match source {
+            Some(source) => self.sources.insert(name.to_owned(), source),
+            None => self.sources.remove(name),
+        };
+        Ok(())
+    }
+
+    /// replaces the table `name` by `view` over it and keeps `view` to re-apply it when `name` is
+    /// registered again. The table is unchanged if `view` is invalid over it.
+    fn add_view(&mut self, name: &str, view: ViewDefinition) -> PyResult<()> {
+        let table: Arc<dyn TableProvider + Send + Sync> =
+            match self.ctx.state.datasources.remove(name) {
+                Some(table) => table.into(),
+                None => return Err(errors::DataFusionError::TableNotFound(name.to_owned()).into()),
+            };
+        match view.apply(table.clone()) {
+            Ok(table) => self.ctx.register_table(name, Box::new(table)),
+            Err(e) => {
+                self.ctx.register_table(name, Box::new(SharedTable(table)));
+                return Err(errors::DataFusionError::from(e).into());
+            }
+        };
+        self.views.entry(name.to_owned()).or_default().push(view);
+        Ok(())
+    }
+
+    /// the batches of `query_or_df`, a SQL statement or a DataFrame, computed one at a time
+    fn stream_of(
+        &mut self,
+        query_or_df: &PyAny,
+        py: Python,
+    ) -> PyResult<stream::RecordBatchStream> {
+        match query_or_df.extract::<&str>() {
+            Ok(query) => self.sql_iter(query, None, py),
+            Err(_) => query_or_df
+                .extract::<PyRef<dataframe::DataFrame>>()?
+                .iter_batches(None, py),
+        }
+    }
+
+    /// the schema of the registered table `name`
+    fn table_schema(&self, name: &str) -> PyResult<SchemaRef> {
+        match self.ctx.state.datasources.get(name) {
+            Some(table) => Ok(table.schema()),
+            None => Err(errors::DataFusionError::TableNotFound(name.to_owned()).into()),
+        }
+    }
+
+    /// plans `query`, checking it against `policy` before any of it runs (e.g. a
+    /// `CREATE EXTERNAL TABLE`). `exempt_tables` are allowed regardless of the policy.
+    fn plan_sql(
+        &mut self,
+        query: &str,
+        policy: Option<&SqlPolicy>,
+        exempt_tables: &[String],
+        py: Python,
+    ) -> PyResult<LogicalPlan> {
+        if let Some(policy) = policy {
+            let plan = self
+                .ctx
+                .create_logical_plan(query)
+                .map_err(|e| suggestions::with_suggestions(e, query, &self.ctx.state))?;
+            policy.check(&plan, exempt_tables)?;
+        }
+        // statements such as `CREATE EXTERNAL TABLE` read files while planned
+        let ctx = &mut self.ctx;
+        let plan = py.allow_threads(move || ctx.sql(query).map(|df| df.to_logical_plan()));
+        let plan = plan.map_err(|e| suggestions::with_suggestions(e, query, &self.ctx.state))?;
+        if self.ordered_aggregates {
+            Ok(order_aggregates(plan)?)
+        } else {
+            Ok(plan)
+        }
+    }
+
+    /// registers `partitions` as a table and returns a DataFrame that scans it
+    fn create_dataframe_from_batches(
+        &mut self,
+        partitions: Vec<Vec<RecordBatch>>,
+    ) -> PyResult<dataframe::DataFrame> {
+        let table = memory_table(partitions)?;
+        self.create_dataframe_from_table(Box::new(table))
+    }
+
+    /// registers `table` with a random (unique) name and returns a DataFrame that scans it
+    fn create_dataframe_from_table(
+        &mut self,
+        table: Box<dyn TableProvider + Send + Sync>,
+    ) -> PyResult<dataframe::DataFrame> {
+        // generate a random (unique) name for this table
+        let name = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(10)
+            .collect::<String>();
+
+        self.ctx.register_table(&name, table);
+        Ok(dataframe::DataFrame::new(
+            self.ctx.state.clone(),
+            errors::wrap(self.ctx.table(&name))?.to_logical_plan(),
+            self.runtime,
+            self.metrics.clone(),
+            self.admission.clone(),
+        ))
+    }
+}
+
+/// converts lists of pyarrow RecordBatches into lists of RecordBatches
+fn to_rust_partitions(
+    partitions: Vec<Vec<PyObject>>,
+    py: Python,
+) -> PyResult<Vec<Vec<RecordBatch>>> {
+    partitions
+        .iter()
+        .map(|batches| {
+            batches
+                .iter()
+                .map(|batch| to_rust::to_rust_batch(batch.as_ref(py)))
+                .collect()
+        })
+        .collect()
+}
+
+/// converts a dictionary mapping column names to sequences of values (e.g. numpy arrays) into
+/// RecordBatches, inferring the schema from the values
+fn pydict_to_batches(data: &PyDict, py: Python) -> PyResult<Vec<RecordBatch>> {
+    let pyarrow = py.import("pyarrow")?;
+    let table = pyarrow
+        .getattr("Table")?
+        .call_method1("from_pydict", (data,))?;
+    table
+        .call_method0("to_batches")?
+        .iter()?
+        .map(|batch| to_rust::to_rust_batch(batch?))
+        .collect()
+}
+
+/// an in-memory table with `partitions`, which must have at least one batch
+fn memory_table(partitions: Vec<Vec<RecordBatch>>) -> PyResult<MemTable> {
+    let schema = match partitions.iter().flatten().next() {
+        Some(batch) => batch.schema(),
+        None => {
+            return Err(errors::DataFusionError::Common(
+                "A table requires at least one record batch".to_owned(),
+            )
+            .into())
+        }
+    };
+    Ok(errors::wrap(MemTable::new(schema, partitions))?)
+}
+
+/// whether `plan` aggregates rows by some group keys
+fn has_groups(plan: &LogicalPlan) -> bool {
+    match plan {
+        LogicalPlan::Aggregate { group_expr, .. } if !group_expr.is_empty() => true,
+        _ => utils::inputs(plan).into_iter().any(has_groups),
+    }
+}
+
+/// sorts the result of `plan` by all its columns when it aggregates by some group keys and is
+/// not already sorted, so that its order doesn't depend on the order of the groups in memory
+fn order_aggregates(plan: LogicalPlan) -> PyResult<LogicalPlan> {
+    Ok(match plan {
+        LogicalPlan::Sort { .. } => plan,
+        LogicalPlan::Limit { n, input } => LogicalPlan::Limit {
+            n,
+            input: Arc::new(order_aggregates(input.as_ref().clone())?),
+        },
+        plan if has_groups(&plan) => {
+            let sort = plan
+                .schema()
+                .fields()
+                .iter()
+                .map(|field| Expr::Sort {
+                    expr: Box::new(col(field.name())),
+                    asc: true,
+                    nulls_first: true,
+                })
+                .collect();
+            LogicalPlanBuilder::from(&plan).sort(sort)?.build()?
+        }
+        plan => plan,
+    })
 }
@@ -1,12 +1,30 @@
+use std::sync::Arc;
+use std::time::Instant;
+
 use logical_plan::LogicalPlan;
-use pyo3::{prelude::*, types::PyTuple};
-use tokio::runtime::Runtime;
+use pyo3::{
+    exceptions,
+    prelude::*,
+    types::{PyBytes, PyDict, PyTuple},
+};
+
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
 
 use datafusion::execution::context::ExecutionContext as _ExecutionContext;
-use datafusion::logical_plan::LogicalPlanBuilder;
+use datafusion::logical_plan::{Expr, JoinType, LogicalPlanBuilder};
+use datafusion::physical_plan::{common, ExecutionPlan};
 use datafusion::{execution::context::ExecutionContextState, logical_plan};
 
+use crate::admission::Admission;
+use crate::explain;
 use crate::expression;
+use crate::metrics::{Metrics, QueryStats};
+use crate::runtime::{batch_size_of, RuntimeConfig};
+use crate::sample;
+use crate::stream;
+use crate::unparser;
+use crate::writer;
 use crate::{errors, to_py};
 
 /// A DataFrame is a representation of a logical plan and an API to compose statements.
@@ -16,12 +34,52 @@ use crate::{errors, to_py};
 pub(crate) struct DataFrame {
     ctx_state: ExecutionContextState,
     plan: LogicalPlan,
+    runtime: RuntimeConfig,
+    metrics: Arc<Metrics>,
+    admission: Arc<Admission>,
+    /// the maximum number of rows of the result, which is truncated beyond it
+    max_rows: Option<usize>,
+    /// the fraction of the rows of the tables read by the plan, when they are sampled
+    sampled_fraction: Option<f64>,
 }
 
 impl DataFrame {
     /// creates a new DataFrame
-    pub fn new(ctx_state: ExecutionContextState, plan: LogicalPlan) -> Self {
-        Self { ctx_state, plan }
+    pub fn new(
+        ctx_state: ExecutionContextState,
+        plan: LogicalPlan,
+        runtime: RuntimeConfig,
+        metrics: Arc<Metrics>,
+        admission: Arc<Admission>,
+    ) -> Self {
+        Self {
+            ctx_state,
+            plan,
+            runtime,
+            metrics,
+            admission,
+            max_rows: None,
+            sampled_fraction: None,
+        }
+    }
+
+    /// limits the result of this DataFrame (and of the DataFrames created from it) to `max_rows`
+    pub fn with_max_rows(mut self, max_rows: Option<usize>) -> Self {
+        self.max_rows = max_rows;
+        self
+    }
+
+    /// creates a new DataFrame with the same context as this one
+    fn with_plan(&self, plan: LogicalPlan) -> Self {
+        Self {
+            ctx_state: self.ctx_state.clone(),
+            plan,
+            runtime: self.runtime,
+            metrics: self.metrics.clone(),
+            admission: self.admission.clone(),
+            max_rows: self.max_rows,
+            sampled_fraction: self.sampled_fraction,
+        }
     }
 }
 
@@ -36,10 +94,7 @@ impl DataFrame {
             errors::wrap(builder.project(expressions.iter().map(|e| e.expr.clone()).collect()))?;
         let plan = errors::wrap(builder.build())?;
 
-        Ok(DataFrame {
-            ctx_state: self.ctx_state.clone(),
-            plan,
-        })
+        Ok(self.with_plan(plan))
     }
 
     /// Filter according to the `predicate` expression
@@ -48,10 +103,7 @@ impl DataFrame {
         let builder = errors::wrap(builder.filter(predicate.expr))?;
         let plan = errors::wrap(builder.build())?;
 
-        Ok(DataFrame {
-            ctx_state: self.ctx_state.clone(),
-            plan,
-        })
+        Ok(self.with_plan(plan))
     }
 
     /// Aggregates using expressions
@@ -67,10 +119,25 @@ impl DataFrame {
         ))?;
         let plan = errors::wrap(builder.build())?;
 
-        Ok(DataFrame {
-            ctx_state: self.ctx_state.clone(),
-            plan,
-        })
+        Ok(self.with_plan(plan))
+    }
+
+    /// Returns an approximation of this DataFrame reading a random sample of about `fraction` of
+    /// the rows of each table, e.g. for fast approximate aggregates while exploring large tables.
+    /// Counts and sums are not scaled: divide them by `sampled_fraction` to estimate the exact
+    /// values.
+    #[args(fraction = "0.01")]
+    pub(crate) fn approx(&self, fraction: f64) -> PyResult<Self> {
+        let mut df = self.with_plan(sample::sample(&self.plan, fraction)?);
+        df.sampled_fraction = Some(self.sampled_fraction.unwrap_or(1.0) * fraction);
+        Ok(df)
+    }
+
+    /// The fraction of the rows of the tables that the plan reads, if they are sampled (see
+    /// `approx`), `None` otherwise.
+    #[getter]
+    fn sampled_fraction(&self) -> Option<f64> {
+        self.sampled_fraction
     }
 
     /// Limits the plan to return at most `count` rows
@@ -79,30 +146,517 @@ impl DataFrame {
         let builder = errors::wrap(builder.limit(count))?;
         let plan = errors::wrap(builder.build())?;
 
-        Ok(DataFrame {
-            ctx_state: self.ctx_state.clone(),
-            plan,
-        })
+        Ok(self.with_plan(plan))
+    }
+
+    /// Sorts by `expressions`, in order. Expressions are sorted ascending unless created with
+    /// `Expression.sort`, e.g. `df.sort(f.col("a").sort(ascending=False))`.
+    #[args(args = "*")]
+    fn sort(&self, args: &PyTuple) -> PyResult<Self> {
+        let expressions = expression::from_tuple(args)?
+            .into_iter()
+            .map(|e| match e.expr {
+                sort @ Expr::Sort { .. } => sort,
+                expr => Expr::Sort {
+                    expr: Box::new(expr),
+                    asc: true,
+                    nulls_first: true,
+                },
+            })
+            .collect();
+        let builder = LogicalPlanBuilder::from(&self.plan);
+        let builder = errors::wrap(builder.sort(expressions))?;
+        let plan = errors::wrap(builder.build())?;
+
+        Ok(self.with_plan(plan))
+    }
+
+    /// Joins this DataFrame with `right` on the equality of `join_keys`, a pair
+    /// `(left columns, right columns)`. `how` is one of "inner", "left", "right" or "cross".
+    /// `filter` is an expression applied to the joined rows, which allows joining on arbitrary
+    /// (e.g. range) conditions; it is only valid on inner joins.
+    /// Without `join_keys`, every row of this DataFrame is joined with every row of `right` (a
+    /// cartesian product), which is usually a mistake: `cross_join` is what to do then, either
+    /// `"error"`, `"warn"` (with a `RuntimeWarning`) or `"allow"`. With `how="cross"`, the
+    /// cartesian product is explicit: `join_keys` must be empty and `cross_join` is ignored.
+    #[args(how = "\"inner\"", filter = "None", cross_join = "\"error\"")]
+    fn join(
+        &self,
+        right: &DataFrame,
+        join_keys: (Vec<String>, Vec<String>),
+        how: &str,
+        filter: Option<expression::Expression>,
+        cross_join: &str,
+        py: Python,
+    ) -> PyResult<Self> {
+        let join_type = match how {
+            "inner" => JoinType::Inner,
+            "left" => JoinType::Left,
+            "right" => JoinType::Right,
+            // an inner join without keys
+            "cross" => JoinType::Inner,
+            how => {
+                return Err(errors::DataFusionError::Common(format!(
+                    "The join type \"{}\" does not exist or is not implemented",
+                    how
+                ))
+                .into())
+            }
+        };
+        if how == "cross" && !(join_keys.0.is_empty() && join_keys.1.is_empty()) {
+            return Err(errors::DataFusionError::Common(
+                "A cross join has no join keys".to_owned(),
+            )
+            .into());
+        }
+        if filter.is_some() && !["inner", "cross"].contains(&how) {
+            return Err(errors::DataFusionError::Common(
+                "A join filter can only be used on inner joins".to_owned(),
+            )
+            .into());
+        }
+
+        if !["error", "warn", "allow"].contains(&cross_join) {
+            return Err(errors::DataFusionError::Common(format!(
+                "The value \"{}\" of cross_join is not valid. Valid values are \"error\", \
+                 \"warn\" and \"allow\"",
+                cross_join
+            ))
+            .into());
+        }
+        if how != "cross" && join_keys.0.is_empty() && join_keys.1.is_empty() {
+            let message = "The join has no keys and results in the cartesian product of both \
+                           DataFrames";
+            match cross_join {
+                "error" => {
+                    return Err(errors::DataFusionError::Common(format!(
+                        "{}. Use `cross_join=\"allow\"` if this is intended",
+                        message
+                    ))
+                    .into())
+                }
+                "warn" => {
+                    let warning = py.get_type::<exceptions::PyRuntimeWarning>();
+                    py.import("warnings")?
+                        .call_method1("warn", (message, warning))?;
+                }
+                _ => {}
+            }
+        }
+
+        let left_keys = join_keys
+            .0
+            .iter()
+            .map(|s| s.as_ref())
+            .collect::<Vec<&str>>();
+        let right_keys = join_keys
+            .1
+            .iter()
+            .map(|s| s.as_ref())
+            .collect::<Vec<&str>>();
+
+        let builder = LogicalPlanBuilder::from(&self.plan);
+        let builder = errors::wrap(builder.join(&right.plan, join_type, &left_keys, &right_keys))?;
+        let builder = match filter {
+            Some(filter) => errors::wrap(builder.filter(filter.expr))?,
+            None => builder,
+        };
+        let plan = errors::wrap(builder.build())?;
+
+        Ok(self.with_plan(plan))
+    }
+
+    /// Returns a description of the logical plan, either as text or, with `format="json"`,
+    /// as a JSON document.
+    /// With `verbose`, the text also describes the optimized logical plan and the physical plan
+    /// that executes it, e.g. to check which filters were pushed down to the scans.
+    /// Planning scans tables, which runs the query of tables registered with `register_dbapi`
+    /// and the `scan` of tables registered with `register_provider`: when the plan reads such
+    /// tables, the physical plan is not described, and the text names the tables instead.
+    #[args(format = "\"text\"", verbose = "false")]
+    pub(crate) fn explain(&self, format: &str, verbose: bool) -> PyResult<String> {
+        match (format, verbose) {
+            ("text", false) => Ok(format!("{:?}", self.plan)),
+            ("text", true) => {
+                let ctx = _ExecutionContext::from(self.ctx_state.clone());
+                let optimized = errors::wrap(ctx.optimize(&self.plan))?;
+                let python_tables = explain::python_scans(&optimized, &self.ctx_state);
+                let physical = if python_tables.is_empty() {
+                    format!("{:?}", errors::wrap(ctx.create_physical_plan(&optimized))?)
+                } else {
+                    format!(
+                        "not planned: planning would scan the tables backed by Python code \"{}\"",
+                        python_tables.join("\", \"")
+                    )
+                };
+                Ok(format!(
+                    "logical plan:\n{:?}\n\noptimized logical plan:\n{:?}\n\nphysical plan:\n{}",
+                    self.plan, optimized, physical
+                ))
+            }
+            ("json", false) => Ok(explain::plan_to_json(&self.plan).to_string()),
+            ("json", true) => Err(errors::DataFusionError::Common(
+                "Verbose explanations are only available as text".to_owned(),
+            )
+            .into()),
+            (other, _) => Err(errors::DataFusionError::Common(format!(
+                "The format \"{}\" is not valid. Valid formats are \"text\" and \"json\"",
+                other
+            ))
+            .into()),
+        }
+    }
+
+    /// Returns the logical plan after each rule of the optimizer, as a list of
+    /// `(rule name, plan)`. This shows which rule changed (or did not change) the plan.
+    /// The last plan is the optimized plan that is executed.
+    fn optimizer_trace(&self) -> PyResult<Vec<(String, String)>> {
+        let ctx = _ExecutionContext::from(self.ctx_state.clone());
+        let trace = errors::wrap(explain::optimizer_trace(&ctx, &self.plan))?;
+        Ok(trace
+            .into_iter()
+            .map(|(rule, plan)| (rule, format!("{:?}", plan)))
+            .collect())
+    }
+
+    /// Returns a SQL query equivalent to this DataFrame's plan.
+    fn to_sql(&self) -> PyResult<String> {
+        Ok(unparser::plan_to_sql(&self.plan)?)
     }
 
     /// Executes the plan, returning a list of `RecordBatch`es.
     /// Unless some order is specified in the plan, there is no guarantee of the order of the result
-    fn collect(&self) -> PyResult<PyObject> {
-        let mut rt = Runtime::new().unwrap();
+    /// `converters` is an optional dictionary mapping column names to the type they are casted
+    /// to: a pyarrow type or the name of a numpy dtype, e.g. `{"ts": "datetime64[ms]"}`.
+    #[args(converters = "None")]
+    fn collect(&self, converters: Option<&PyDict>, py: Python) -> PyResult<PyObject> {
+        let (schema, batches) = self.execute(py)?;
+        to_py::to_py(&schema, &batches, converters, py)
+    }
 
-        let ctx = _ExecutionContext::from(self.ctx_state.clone());
-        let plan = ctx
-            .optimize(&self.plan)
-            .map_err(|e| -> errors::DataFusionError { e.into() })?;
-        let plan = ctx
-            .create_physical_plan(&plan)
-            .map_err(|e| -> errors::DataFusionError { e.into() })?;
-
-        let batches = rt.block_on(async {
-            ctx.collect(plan)
-                .await
-                .map_err(|e| -> errors::DataFusionError { e.into() })
+    /// Executes the plan lazily, returning an iterator of `RecordBatch`es, e.g. to consume results
+    /// that don't fit in memory: each batch is only computed when requested.
+    /// `batch_size` is the number of rows of the batches read from tables.
+    #[args(batch_size = "None")]
+    pub(crate) fn iter_batches(
+        &self,
+        batch_size: Option<usize>,
+        py: Python,
+    ) -> PyResult<stream::RecordBatchStream> {
+        if batch_size == Some(0) {
+            return Err(errors::DataFusionError::Common(
+                "The batch size must be at least 1".to_owned(),
+            )
+            .into());
+        }
+        let admission = &self.admission;
+        let permit = py.allow_threads(move || admission.acquire())?;
+
+        let runtime = self.runtime.build()?;
+        let (_, plan) = self.create_physical_plan(self.max_rows, batch_size)?;
+        Ok(stream::RecordBatchStream::new(
+            runtime,
+            plan,
+            self.metrics.clone(),
+            permit,
+        ))
+    }
+
+    /// Executes the plan, returning the result as a `pyarrow.Table`.
+    fn to_arrow(&self, py: Python) -> PyResult<PyObject> {
+        let (schema, batches) = self.execute(py)?;
+        to_py::to_py_table(&schema, &batches, py)
+    }
+
+    /// Executes the plan, returning the result as a `pandas.DataFrame`.
+    fn to_pandas(&self, py: Python) -> PyResult<PyObject> {
+        let (schema, batches) = self.execute(py)?;
+        to_py::to_py_table(&schema, &batches, py)?.call_method0(py, "to_pandas")
+    }
+
+    /// Executes the plan, returning a tuple with the list of `RecordBatch`es and a dictionary with
+    /// statistics of the execution: `planning_seconds`, `execution_seconds`, `output_rows`,
+    /// `output_batches`, `output_bytes` and whether the result was `truncated` to its maximum
+    /// number of rows (see `ExecutionContext.sql`).
+    /// Peak memory, spilled bytes and CPU time are not available: DataFusion doesn't measure the
+    /// memory or CPU used by an execution and never spills to disk.
+    fn collect_with_stats(&self, py: Python) -> PyResult<PyObject> {
+        let (schema, batches, stats) = self.execute_with_stats(py)?;
+        Ok((
+            to_py::to_py(&schema, &batches, None, py)?,
+            stats.to_dict(py)?,
+        )
+            .to_object(py))
+    }
+
+    /// Executes the plan, returning the number of rows of each of the partitions of the result,
+    /// without converting it, e.g. to decide how to repartition it before writing it.
+    fn partition_row_counts(&self, py: Python) -> PyResult<Vec<usize>> {
+        let (_, partitions, _) = self.execute_partitions(true, py)?;
+        Ok(partitions
+            .iter()
+            .map(|batches| batches.iter().map(|batch| batch.num_rows()).sum())
+            .collect())
+    }
+
+    /// Executes the plan, returning the result in Arrow's IPC streaming format.
+    fn to_ipc(&self, py: Python) -> PyResult<PyObject> {
+        let (schema, batches) = self.execute(py)?;
+        let bytes = writer::to_ipc_stream(&schema, &batches)?;
+        Ok(PyBytes::new(py, &bytes).to_object(py))
+    }
+
+    /// Executes the plan, writing the result as parquet to `target`: a path (a `str` or an
+    /// `os.PathLike`, e.g. a `pathlib.Path`), a file-like object (with a `write` method) or,
+    /// when `None`, returned as `bytes`.
+    /// Paths are written atomically: they only appear once completely written.
+    /// With `partitioned`, `path` is a directory with a file `part-{i}.parquet` per partition of
+    /// the result and an empty `_SUCCESS` file. The files are written concurrently, at most
+    /// `max_open_files` at a time (by default, all of them). With `rows_per_file`, the directory
+    /// has a file per `rows_per_file` rows of the result instead of one per partition.
+    /// `mode` is what to do when `path` exists: `"overwrite"` it, `"append"` new files to it
+    /// (partitioned writes only) or `"error_if_exists"`.
+    /// With `verify`, the number of rows in the footer of each written file is checked (its data
+    /// pages are not decoded) and a manifest is returned: a dictionary with the `rows` written
+    /// and the `files`, each with its `path`, `size`, `rows` and `sha256`.
+    /// `partitioned`, `verify` and a `mode` other than `"overwrite"` require `target` to be a path.
+    #[args(
+        target = "None",
+        verify = "false",
+        partitioned = "false",
+        mode = "\"overwrite\"",
+        max_open_files = "None",
+        rows_per_file = "None"
+    )]
+    fn write_parquet(
+        &self,
+        target: Option<&PyAny>,
+        verify: bool,
+        partitioned: bool,
+        mode: &str,
+        max_open_files: Option<usize>,
+        rows_per_file: Option<usize>,
+        py: Python,
+    ) -> PyResult<PyObject> {
+        let mode = writer::WriteMode::parse(mode)?;
+        let path = match target {
+            Some(target) => writer::to_path(target, py)?,
+            None => None,
+        };
+        let is_path = path.is_some();
+        if max_open_files == Some(0) {
+            return Err(errors::DataFusionError::Common(
+                "max_open_files must be at least 1".to_owned(),
+            )
+            .into());
+        }
+        if rows_per_file == Some(0) {
+            return Err(errors::DataFusionError::Common(
+                "rows_per_file must be at least 1".to_owned(),
+            )
+            .into());
+        }
+        if rows_per_file.is_some() && !partitioned {
+            return Err(errors::DataFusionError::Common(
+                "rows_per_file requires a partitioned write".to_owned(),
+            )
+            .into());
+        }
+        if (verify || partitioned || mode != writer::WriteMode::Overwrite) && !is_path {
+            return Err(errors::DataFusionError::Common(
+                "Writes can only be verified, partitioned or given a mode when the target is a \
+                 path"
+                    .to_owned(),
+            )
+            .into());
+        }
+        let (schema, partitions, _) = self.execute_partitions(partitioned, py)?;
+        let partitions = match rows_per_file {
+            Some(rows_per_file) => writer::split_rows(schema.clone(), &partitions, rows_per_file)?,
+            None => partitions,
+        };
+        match (target, path) {
+            (_, Some(path)) => {
+                let path = path.as_str();
+                // other threads can run while the files are written
+                let files = py.allow_threads(|| {
+                    if partitioned {
+                        writer::write_parquet_dataset(
+                            path,
+                            schema,
+                            &partitions,
+                            mode,
+                            max_open_files,
+                        )
+                    } else {
+                        writer::write_parquet_path(path, schema, &partitions.concat(), mode)
+                    }
+                })?;
+                if verify {
+                    let files = files
+                        .iter()
+                        .map(|(path, rows)| writer::verify_parquet_file(path, *rows))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    return writer::manifest(&files, py);
+                }
+                Ok(py.None())
+            }
+            (Some(file), None) => {
+                let bytes = writer::to_parquet_bytes(schema, &partitions.concat())?;
+                file.call_method1("write", (PyBytes::new(py, &bytes),))?;
+                Ok(py.None())
+            }
+            (None, None) => {
+                let bytes = writer::to_parquet_bytes(schema, &partitions.concat())?;
+                Ok(PyBytes::new(py, &bytes).to_object(py))
+            }
+        }
+    }
+
+    /// Executes the plan, writing the result to `path` in Arrow's IPC file format.
+    /// `path` only appears once completely written.
+    /// `mode` is what to do when `path` exists: `"overwrite"` it or `"error_if_exists"`.
+    /// With `verify`, the written file is read back to check its number of rows and a manifest
+    /// is returned, see `write_parquet`.
+    #[args(verify = "false", mode = "\"overwrite\"")]
+    fn write_ipc(&self, path: &str, verify: bool, mode: &str, py: Python) -> PyResult<PyObject> {
+        let mode = writer::WriteMode::parse(mode)?;
+        mode.check_file(path)?;
+        let (schema, batches) = self.execute(py)?;
+        writer::write_atomically(path, |temporary| {
+            writer::write_ipc_file(temporary, &schema, &batches)
         })?;
-        to_py::to_py(&batches)
+        if verify {
+            let rows = batches.iter().map(|b| b.num_rows() as u64).sum();
+            let file = writer::verify_ipc_file(path, rows)?;
+            return writer::manifest(&[file], py);
+        }
+        Ok(py.None())
+    }
+}
+impl DataFrame {
+    /// optimizes, plans and executes the plan, returning the schema and the batches of the result
+    fn execute(
+        &self,
+        py: Python,
+    ) -> Result<(SchemaRef, Vec<RecordBatch>), errors::DataFusionError> {
+        let (schema, batches, _) = self.execute_with_stats(py)?;
+        Ok((schema, batches))
+    }
+
+    /// executes the plan, also returning the statistics of the execution
+    fn execute_with_stats(
+        &self,
+        py: Python,
+    ) -> Result<(SchemaRef, Vec<RecordBatch>, QueryStats), errors::DataFusionError> {
+        let (schema, mut partitions, stats) = self.execute_partitions(false, py)?;
+        Ok((schema, partitions.pop().unwrap_or_default(), stats))
+    }
+
+    /// optimizes and plans the plan, limited to `limit` rows and reading batches of `batch_size`
+    /// rows (by default, of the runtime's `target_batch_bytes` or of the context), returning the
+    /// physical plan and the context to execute it
+    fn create_physical_plan(
+        &self,
+        limit: Option<usize>,
+        batch_size: Option<usize>,
+    ) -> Result<(_ExecutionContext, Arc<dyn ExecutionPlan>), errors::DataFusionError> {
+        let mut ctx = _ExecutionContext::from(self.ctx_state.clone());
+        let plan = match limit {
+            Some(limit) => LogicalPlanBuilder::from(&self.plan).limit(limit)?.build()?,
+            None => self.plan.clone(),
+        };
+        let plan = ctx.optimize(&plan)?;
+        if let Some(batch_size) = batch_size {
+            ctx.state.config.batch_size = batch_size;
+        } else if let Some(target_bytes) = self.runtime.target_batch_bytes {
+            ctx.state.config.batch_size = batch_size_of(&plan, target_bytes);
+        }
+        let plan = ctx.create_physical_plan(&plan)?;
+        Ok((ctx, plan))
+    }
+
+    /// executes the plan, returning the batches of each of its output partitions when
+    /// `partitioned` (and of a single partition otherwise) and the statistics of the execution
+    pub(crate) fn execute_partitions(
+        &self,
+        partitioned: bool,
+        py: Python,
+    ) -> Result<(SchemaRef, Vec<Vec<RecordBatch>>, QueryStats), errors::DataFusionError> {
+        // other threads can run (and finish their queries) while this one waits for its turn
+        let admission = &self.admission;
+        let _permit = py.allow_threads(move || admission.acquire())?;
+
+        let mut rt = self.runtime.build()?;
+
+        let start = Instant::now();
+        // one more row tells whether the result was truncated
+        let (ctx, plan) =
+            self.create_physical_plan(self.max_rows.map(|max_rows| max_rows + 1), None)?;
+        let schema = plan.schema();
+        let planning = start.elapsed();
+
+        let start = Instant::now();
+        // other threads run while this one waits for the result; UDFs reacquire the GIL on the
+        // threads that call them
+        let partitions = py.allow_threads(move || {
+            rt.block_on(async {
+                if partitioned {
+                    let mut partitions = vec![];
+                    for partition in 0..plan.output_partitioning().partition_count() {
+                        partitions.push(common::collect(plan.execute(partition).await?).await?);
+                    }
+                    Ok::<_, errors::DataFusionError>(partitions)
+                } else {
+                    Ok(vec![ctx.collect(plan).await?])
+                }
+            })
+        });
+        let execution = start.elapsed();
+
+        let partitions: Vec<Vec<RecordBatch>> = match partitions {
+            Ok(partitions) => partitions,
+            Err(e) => {
+                self.metrics.record(None, execution);
+                return Err(e);
+            }
+        };
+        let (partitions, truncated) = match self.max_rows {
+            Some(max_rows) => truncate(schema.clone(), partitions, max_rows)?,
+            None => (partitions, false),
+        };
+        let batches = partitions.concat();
+        self.metrics.record(Some(&batches), execution);
+        let mut stats = QueryStats::new(planning, execution, &batches);
+        stats.truncated = truncated;
+        Ok((schema, partitions, stats))
+    }
+}
+
+/// keeps the first `max_rows` rows of `partitions`, returning whether any row was dropped
+fn truncate(
+    schema: SchemaRef,
+    partitions: Vec<Vec<RecordBatch>>,
+    max_rows: usize,
+) -> Result<(Vec<Vec<RecordBatch>>, bool), errors::DataFusionError> {
+    let mut remaining = max_rows;
+    let mut truncated = false;
+    let mut result = vec![];
+    for batches in partitions {
+        let mut partition = vec![];
+        for batch in batches {
+            if batch.num_rows() <= remaining {
+                remaining -= batch.num_rows();
+                partition.push(batch);
+            } else {
+                truncated = true;
+                if remaining > 0 {
+                    partition.push(writer::take_rows(schema.clone(), &batch, 0, remaining)?);
+                    remaining = 0;
+                }
+            }
+        }
+        result.push(partition);
     }
+    Ok((result, truncated))
 }
@@ -0,0 +1,253 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use pyo3::prelude::*;
+
+use arrow::datatypes::{Field, Schema, SchemaRef};
+use arrow::json;
+use arrow::record_batch::RecordBatch;
+use datafusion::datasource::csv::CsvFile;
+use datafusion::datasource::parquet::ParquetTable;
+use datafusion::datasource::{MemTable, TableProvider};
+use datafusion::logical_plan::col;
+use datafusion::physical_plan::csv::CsvReadOptions;
+
+use crate::errors;
+use crate::types::PyDataType;
+use crate::view::ViewTable;
+
+/// The origin of a table registered from files, used to re-create it (e.g. to pick up a new
+/// schema after files changed).
+#[derive(Debug, Clone)]
+pub(crate) enum TableSource {
+    Parquet {
+        path: String,
+        /// the columns exposed by the table, all if `None`
+        columns: Option<Vec<String>>,
+    },
+    Csv {
+        path: String,
+        schema: Option<Vec<(String, PyDataType)>>,
+        has_header: bool,
+        delimiter: String,
+    },
+    Json {
+        path: String,
+        schema: Option<Vec<(String, PyDataType)>>,
+        schema_infer_max_records: usize,
+        batch_size: usize,
+    },
+}
+
+impl TableSource {
+    /// creates a new table from this source
+    pub fn to_table(&self) -> PyResult<Box<dyn TableProvider + Send + Sync>> {
+        Ok(match self {
+            TableSource::Parquet { path, columns } => {
+                let table = parquet_table(path)?;
+                match columns {
+                    Some(columns) => Box::new(project_table(Arc::new(table), columns)?),
+                    None => Box::new(table),
+                }
+            }
+            TableSource::Csv {
+                path,
+                schema,
+                has_header,
+                delimiter,
+            } => Box::new(csv_table(path, schema.clone(), *has_header, delimiter)?),
+            TableSource::Json {
+                path,
+                schema,
+                schema_infer_max_records,
+                batch_size,
+            } => Box::new(json_table(
+                path,
+                schema.clone(),
+                *schema_infer_max_records,
+                *batch_size,
+            )?),
+        })
+    }
+}
+
+/// returns the source of a dataset at `path` in the format given by `extension`, if supported
+fn source_of(path: &Path, extension: &str) -> Option<TableSource> {
+    let path = path.to_string_lossy().into_owned();
+    match extension {
+        "parquet" => Some(TableSource::Parquet {
+            path,
+            columns: None,
+        }),
+        "csv" => Some(TableSource::Csv {
+            path,
+            schema: None,
+            has_header: true,
+            delimiter: ",".to_owned(),
+        }),
+        _ => None,
+    }
+}
+
+/// returns the extension of a file, if it is a supported format
+fn extension_of(path: &Path) -> Option<&str> {
+    if path.is_dir() {
+        return None;
+    }
+    path.extension()
+        .and_then(|e| e.to_str())
+        .filter(|e| *e == "parquet" || *e == "csv")
+}
+
+/// names a dataset after its path relative to `root`, e.g. `sales/2020.csv` -> `sales_2020`
+fn dataset_name(root: &Path, path: &Path) -> String {
+    let path = path.strip_prefix(root).unwrap_or(path).with_extension("");
+    path.to_string_lossy()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn discover_in(
+    root: &Path,
+    directory: &Path,
+    datasets: &mut Vec<(String, TableSource)>,
+) -> io::Result<()> {
+    let mut entries = fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    // e.g. `_SUCCESS` or `.part-0.parquet.crc`
+    entries.retain(|path| {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        !name.starts_with('_') && !name.starts_with('.')
+    });
+    entries.sort();
+
+    // a (non-root) directory with files of a single format is a dataset
+    let extensions = entries
+        .iter()
+        .map(|e| extension_of(e))
+        .collect::<HashSet<_>>();
+    if directory != root && extensions.len() == 1 {
+        if let Some(Some(extension)) = extensions.into_iter().next() {
+            if let Some(source) = source_of(directory, extension) {
+                datasets.push((dataset_name(root, directory), source));
+            }
+            return Ok(());
+        }
+    }
+
+    for entry in entries {
+        if entry.is_dir() {
+            discover_in(root, &entry, datasets)?;
+        } else if let Some(source) = extension_of(&entry).and_then(|e| source_of(&entry, e)) {
+            datasets.push((dataset_name(root, &entry), source));
+        }
+    }
+    Ok(())
+}
+
+/// Finds the parquet and CSV datasets under `root`, returning `(name, source)` of each.
+/// A directory whose files are all of a single format is one dataset; otherwise each file is.
+/// Datasets are named after their path relative to `root`.
+pub(crate) fn discover(root: &str) -> PyResult<Vec<(String, TableSource)>> {
+    let mut datasets = vec![];
+    discover_in(Path::new(root), Path::new(root), &mut datasets).map_err(|e| {
+        errors::DataFusionError::Common(format!("Unable to list \"{}\": {}", root, e))
+    })?;
+    Ok(datasets)
+}
+
+/// converts a list of `(name, type)` into a (nullable) schema
+pub(crate) fn to_schema(fields: Vec<(String, PyDataType)>) -> Schema {
+    Schema::new(
+        fields
+            .into_iter()
+            .map(|(name, data_type)| Field::new(&name, data_type.data_type, true))
+            .collect(),
+    )
+}
+
+/// a table over a parquet file or a directory of parquet files
+pub(crate) fn parquet_table(path: &str) -> PyResult<ParquetTable> {
+    Ok(errors::wrap(ParquetTable::try_new(path))?)
+}
+
+/// a table with only the `columns` of `table`, in that order
+pub(crate) fn project_table(
+    table: Arc<dyn TableProvider + Send + Sync>,
+    columns: &[String],
+) -> PyResult<ViewTable> {
+    let columns = columns.iter().map(|name| col(name)).collect();
+    Ok(errors::wrap(ViewTable::try_new(
+        table,
+        None,
+        Some(columns),
+    ))?)
+}
+
+/// a table over a CSV file or a directory of CSV files.
+/// The schema is inferred from the file(s) unless `schema` is provided.
+pub(crate) fn csv_table(
+    path: &str,
+    schema: Option<Vec<(String, PyDataType)>>,
+    has_header: bool,
+    delimiter: &str,
+) -> PyResult<CsvFile> {
+    let delimiter = match delimiter.as_bytes() {
+        [delimiter] => *delimiter,
+        _ => {
+            return Err(errors::DataFusionError::Common(format!(
+                "The delimiter must be a single (ASCII) character, got \"{}\"",
+                delimiter
+            ))
+            .into())
+        }
+    };
+    let schema = schema.map(to_schema);
+
+    let mut options = CsvReadOptions::new()
+        .has_header(has_header)
+        .delimiter(delimiter);
+    if let Some(schema) = &schema {
+        options = options.schema(schema);
+    }
+    Ok(errors::wrap(CsvFile::try_new(path, options))?)
+}
+
+/// an in-memory table with the contents of a newline-delimited JSON file.
+/// The schema is inferred from the first `schema_infer_max_records` records unless `schema` is
+/// provided.
+pub(crate) fn json_table(
+    path: &str,
+    schema: Option<Vec<(String, PyDataType)>>,
+    schema_infer_max_records: usize,
+    batch_size: usize,
+) -> PyResult<MemTable> {
+    let file = File::open(path).map_err(|e| {
+        errors::DataFusionError::Common(format!("Unable to open \"{}\": {}", path, e))
+    })?;
+
+    let builder = json::ReaderBuilder::new().with_batch_size(batch_size);
+    let builder = match schema {
+        Some(schema) => builder.with_schema(Arc::new(to_schema(schema))),
+        None => builder.infer_schema(Some(schema_infer_max_records)),
+    };
+    let mut reader = builder
+        .build(file)
+        .map_err(|e| errors::DataFusionError::from(e))?;
+
+    let schema: SchemaRef = reader.schema();
+    let mut batches: Vec<RecordBatch> = vec![];
+    while let Some(batch) = reader
+        .next()
+        .map_err(|e| errors::DataFusionError::from(e))?
+    {
+        batches.push(batch);
+    }
+
+    Ok(errors::wrap(MemTable::new(schema, vec![batches]))?)
+}
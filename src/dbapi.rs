@@ -0,0 +1,157 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use pyo3::prelude::*;
+
+use arrow::datatypes::{Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use datafusion::datasource::TableProvider;
+use datafusion::error::Result;
+use datafusion::physical_plan::{memory::MemoryExec, ExecutionPlan};
+
+use crate::errors;
+use crate::to_rust::to_rust;
+use crate::types::{to_py_data_type, PyDataType};
+
+/// number of rows used to infer the schema of a query
+const SCHEMA_INFER_MAX_RECORDS: usize = 100;
+
+/// A table whose rows are the result of a query against a Python DB-API 2.0 connection
+/// (e.g. `sqlite3`). The query runs on every scan, selecting only the columns the plan uses.
+/// Pushing filters down to the database as a `WHERE` clause is deferred: DataFusion doesn't pass
+/// filters to tables yet, so every scan fetches all the rows of the query and filters them
+/// afterwards.
+/// Errors of the driver are raised as they are.
+pub(crate) struct DbApiTable {
+    connection: PyObject,
+    query: String,
+    schema: SchemaRef,
+}
+
+/// runs `sql` on a new cursor of `connection`, returning the column names and the rows
+fn execute(connection: &PyObject, sql: &str, py: Python) -> PyResult<(Vec<String>, Vec<PyObject>)> {
+    let cursor = connection.call_method0(py, "cursor")?;
+    cursor.call_method1(py, "execute", (sql,))?;
+    let names = cursor
+        .getattr(py, "description")?
+        .as_ref(py)
+        .iter()?
+        .map(|column| column?.get_item(0)?.extract::<String>())
+        .collect::<PyResult<_>>()?;
+    let rows = cursor.call_method0(py, "fetchall")?.extract(py)?;
+    Ok((names, rows))
+}
+
+/// returns the values of the `i`th column of `rows`
+fn column<'a>(rows: &'a [PyObject], i: usize, py: Python<'a>) -> PyResult<Vec<&'a PyAny>> {
+    rows.iter()
+        .map(|row| row.as_ref(py).get_item(i as isize))
+        .collect()
+}
+
+impl DbApiTable {
+    /// Creates a new table from `query`. When `schema` is not provided, it is inferred from the
+    /// first rows of the query.
+    pub fn try_new(
+        connection: PyObject,
+        query: &str,
+        schema: Option<Schema>,
+        py: Python,
+    ) -> PyResult<Self> {
+        let schema = match schema {
+            Some(schema) => schema,
+            None => {
+                let sql = format!(
+                    "SELECT * FROM ({}) AS __datafusion LIMIT {}",
+                    query, SCHEMA_INFER_MAX_RECORDS
+                );
+                let (names, rows) = execute(&connection, &sql, py)?;
+                let pyarrow = py.import("pyarrow")?;
+
+                let fields = names
+                    .iter()
+                    .enumerate()
+                    .map(|(i, name)| {
+                        let values = column(&rows, i, py)?;
+                        let array = pyarrow.call1("array", (values,))?;
+                        let data_type = array.getattr("type")?.extract::<PyDataType>();
+                        match data_type {
+                            Ok(data_type) => Ok(Field::new(name, data_type.data_type, true)),
+                            Err(_) => Err(errors::DataFusionError::Common(format!(
+                                "Unable to infer the type of the column \"{}\"; please provide a schema",
+                                name
+                            ))
+                            .into()),
+                        }
+                    })
+                    .collect::<PyResult<_>>()?;
+                Schema::new(fields)
+            }
+        };
+        Ok(Self {
+            connection,
+            query: query.to_owned(),
+            schema: Arc::new(schema),
+        })
+    }
+
+    /// runs the query selecting only the columns in `projection`
+    fn fetch(&self, projection: &Option<Vec<usize>>, py: Python) -> PyResult<RecordBatch> {
+        let indices = match projection {
+            Some(projection) => projection.clone(),
+            None => (0..self.schema.fields().len()).collect(),
+        };
+        let fields = indices
+            .iter()
+            .map(|i| self.schema.field(*i).clone())
+            .collect::<Vec<_>>();
+        let columns = fields
+            .iter()
+            .map(|field| format!("\"{}\"", field.name().replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let sql = format!("SELECT {} FROM ({}) AS __datafusion", columns, self.query);
+        let (_, rows) = execute(&self.connection, &sql, py)?;
+
+        let pyarrow = py.import("pyarrow")?;
+        let arrays = fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let values = column(&rows, i, py)?;
+                let data_type = to_py_data_type(field.data_type(), py)?;
+                to_rust(pyarrow.call1("array", (values, data_type))?)
+            })
+            .collect::<PyResult<_>>()?;
+
+        let schema = Arc::new(Schema::new(fields));
+        Ok(RecordBatch::try_new(schema, arrays).map_err(|e| errors::DataFusionError::from(e))?)
+    }
+}
+
+impl TableProvider for DbApiTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn scan(
+        &self,
+        projection: &Option<Vec<usize>>,
+        _batch_size: usize,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        // get GIL
+        let gil = pyo3::Python::acquire_gil();
+        let py = gil.python();
+
+        let batch = self
+            .fetch(projection, py)
+            .map_err(|e| errors::python_error(e, py))?;
+        let schema = batch.schema();
+        Ok(Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None)?))
+    }
+}
@@ -1,13 +1,33 @@
 use core::fmt;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
 
 use arrow::error::ArrowError;
 use datafusion::error::ExecutionError;
-use pyo3::{exceptions, PyErr};
+use parquet::errors::ParquetError;
+use pyo3::{exceptions, once_cell::GILOnceCell, PyErr, Python};
+
+/// The Python exceptions raised by this module, besides `NotImplementedError` and `IOError`
+pub mod py {
+    use pyo3::create_exception;
+    use pyo3::exceptions::{PyException, PyIOError, PyKeyError};
+
+    create_exception!(datafusion, DataFusionError, PyException);
+    create_exception!(datafusion, PlanError, DataFusionError);
+    create_exception!(datafusion, TableNotFoundError, PyKeyError);
+    create_exception!(datafusion, ParquetError, PyIOError);
+}
 
 #[derive(Debug)]
 pub enum DataFusionError {
     ExecutionError(ExecutionError),
     ArrowError(ArrowError),
+    ParquetError(ParquetError),
+    /// a statement or plan that can't be planned, e.g. because it references unknown columns
+    Plan(String),
+    /// the name of a table that isn't registered
+    TableNotFound(String),
     Common(String),
 }
 
@@ -16,14 +36,92 @@ impl fmt::Display for DataFusionError {
         match self {
             DataFusionError::ExecutionError(e) => write!(f, "DataFusion error: {:?}", e),
             DataFusionError::ArrowError(e) => write!(f, "Arrow error: {:?}", e),
+            DataFusionError::ParquetError(e) => write!(f, "Parquet error: {:?}", e),
+            DataFusionError::Plan(e) => write!(f, "{}", e),
+            DataFusionError::TableNotFound(name) => {
+                write!(f, "The table \"{}\" does not exist", name)
+            }
             DataFusionError::Common(e) => write!(f, "{}", e),
         }
     }
 }
 
+/// the maximum number of Python exceptions kept by `python_error`: the oldest ones, whose errors
+/// were dropped or never reached Python, are dropped past it
+const MAX_PYTHON_ERRORS: usize = 64;
+
+/// the text that precedes the token of a Python exception in the message of an `ExecutionError`
+const PYTHON_ERROR_TOKEN: &str = "(Python exception #";
+
+/// the Python exceptions raised by Python code called by DataFusion, by the token of the
+/// `ExecutionError` returned to DataFusion in their place
+static PYTHON_ERRORS: GILOnceCell<Mutex<BTreeMap<u64, PyErr>>> = GILOnceCell::new();
+
+/// the last token given to a Python exception
+static LAST_PYTHON_ERROR: AtomicU64 = AtomicU64::new(0);
+
+fn python_errors(py: Python) -> MutexGuard<BTreeMap<u64, PyErr>> {
+    PYTHON_ERRORS
+        .get_or_init(py, || Mutex::new(BTreeMap::new()))
+        .lock()
+        .unwrap()
+}
+
+/// an `ExecutionError` for the exception `error` raised by Python code called by DataFusion
+/// (e.g. a DB-API driver). DataFusion's errors can't carry it: it is kept aside under a unique
+/// token, written in the message of the error, and raised as it is when an error with this
+/// message (possibly wrapped by other errors) reaches Python.
+pub(crate) fn python_error(error: PyErr, py: Python) -> ExecutionError {
+    let token = LAST_PYTHON_ERROR.fetch_add(1, Ordering::Relaxed) + 1;
+    let message = format!("{:?} {}{})", error, PYTHON_ERROR_TOKEN, token);
+    let mut errors = python_errors(py);
+    if errors.len() == MAX_PYTHON_ERRORS {
+        let oldest = *errors.keys().next().unwrap();
+        errors.remove(&oldest);
+    }
+    errors.insert(token, error);
+    ExecutionError::General(message)
+}
+
+/// the Python exception kept by `python_error` for the first token in `message`, if any
+fn take_python_error(message: &str) -> Option<PyErr> {
+    if !message.contains(PYTHON_ERROR_TOKEN) {
+        return None;
+    }
+    let gil = Python::acquire_gil();
+    let mut errors = python_errors(gil.python());
+    message
+        .match_indices(PYTHON_ERROR_TOKEN)
+        .find_map(|(i, prefix)| {
+            let token = message[i + prefix.len()..]
+                .chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect::<String>();
+            errors.remove(&token.parse().ok()?)
+        })
+}
+
 impl From<DataFusionError> for PyErr {
     fn from(err: DataFusionError) -> PyErr {
-        exceptions::PyException::new_err(err.to_string())
+        let message = err.to_string();
+        if let Some(error) = take_python_error(&message) {
+            return error;
+        }
+        match err {
+            DataFusionError::ExecutionError(ExecutionError::NotImplemented(_)) => {
+                exceptions::PyNotImplementedError::new_err(message)
+            }
+            DataFusionError::ExecutionError(ExecutionError::IoError(_)) => {
+                exceptions::PyIOError::new_err(message)
+            }
+            DataFusionError::ExecutionError(ExecutionError::SQL(_)) | DataFusionError::Plan(_) => {
+                py::PlanError::new_err(message)
+            }
+            DataFusionError::ExecutionError(ExecutionError::ParquetError(_))
+            | DataFusionError::ParquetError(_) => py::ParquetError::new_err(message),
+            DataFusionError::TableNotFound(_) => py::TableNotFoundError::new_err(message),
+            _ => py::DataFusionError::new_err(message),
+        }
     }
 }
 
@@ -39,6 +137,12 @@ impl From<ArrowError> for DataFusionError {
     }
 }
 
+impl From<ParquetError> for DataFusionError {
+    fn from(err: ParquetError) -> DataFusionError {
+        DataFusionError::ParquetError(err)
+    }
+}
+
 pub(crate) fn wrap<T>(a: Result<T, ExecutionError>) -> Result<T, DataFusionError> {
     Ok(a?)
 }
@@ -0,0 +1,116 @@
+use serde_json::{json, Value};
+
+use datafusion::datasource::TableProvider;
+use datafusion::error::{ExecutionError, Result};
+use datafusion::execution::context::{ExecutionContext, ExecutionContextState};
+use datafusion::logical_plan::LogicalPlan;
+use datafusion::optimizer::{
+    filter_push_down::FilterPushDown, optimizer::OptimizerRule,
+    projection_push_down::ProjectionPushDown, utils,
+};
+
+use crate::dbapi::DbApiTable;
+use crate::provider::PyTableProvider;
+
+/// returns the name of the node, e.g. `Projection`, from its textual representation
+fn node_name(plan: &LogicalPlan) -> String {
+    let description = format!("{:?}", plan);
+    description
+        .split(|c: char| c == ':' || c.is_whitespace())
+        .next()
+        .unwrap_or_default()
+        .to_owned()
+}
+
+/// Converts a logical plan to a JSON tree where every node has its type (`node`), a
+/// `description`, its `expressions`, its output `schema` and its `inputs`.
+pub(crate) fn plan_to_json(plan: &LogicalPlan) -> Value {
+    let description = format!("{:?}", plan);
+    let schema = plan
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| {
+            json!({
+                "name": field.name(),
+                "type": format!("{:?}", field.data_type()),
+                "nullable": field.is_nullable(),
+            })
+        })
+        .collect::<Vec<_>>();
+    let expressions = utils::expressions(plan)
+        .iter()
+        .map(|expr| format!("{:?}", expr))
+        .collect::<Vec<_>>();
+    let inputs = utils::inputs(plan)
+        .into_iter()
+        .map(plan_to_json)
+        .collect::<Vec<_>>();
+
+    json!({
+        "node": node_name(plan),
+        "description": description.lines().next().unwrap_or_default(),
+        "expressions": expressions,
+        "schema": schema,
+        "inputs": inputs,
+    })
+}
+
+/// The rules of DataFusion's optimizer (`ExecutionContext::optimize`), in the order it applies
+/// them. DataFusion doesn't expose them: `optimizer_trace` fails when they drift from the ones of
+/// the optimizer.
+pub(crate) fn optimizer_rules() -> Vec<Box<dyn OptimizerRule>> {
+    vec![
+        Box::new(ProjectionPushDown::new()),
+        Box::new(FilterPushDown::new()),
+    ]
+}
+
+/// Applies the rules of `optimizer_rules` in order, returning the plan after each rule, as
+/// `(rule name, plan)`. Fails unless the last plan is the plan optimized by `ctx`.
+pub(crate) fn optimizer_trace(
+    ctx: &ExecutionContext,
+    plan: &LogicalPlan,
+) -> Result<Vec<(String, LogicalPlan)>> {
+    let mut traced = plan.clone();
+    let mut trace = vec![];
+    for mut rule in optimizer_rules() {
+        traced = rule.optimize(&traced)?;
+        trace.push((rule.name().to_owned(), traced.clone()));
+    }
+
+    let optimized = ctx.optimize(plan)?;
+    if format!("{:?}", traced) != format!("{:?}", optimized) {
+        return Err(ExecutionError::General(
+            "The rules of the optimizer trace differ from the ones of DataFusion's optimizer"
+                .to_owned(),
+        ));
+    }
+    Ok(trace)
+}
+
+/// whether scanning `table` runs Python code, e.g. a query against a DB-API connection
+fn runs_python(table: &dyn TableProvider) -> bool {
+    let table = table.as_any();
+    table.is::<DbApiTable>() || table.is::<PyTableProvider>()
+}
+
+/// the names of the tables of `state` scanned by `plan` whose scans run Python code. Physical
+/// planning scans them: their Python code runs even if the physical plan is never executed.
+pub(crate) fn python_scans(plan: &LogicalPlan, state: &ExecutionContextState) -> Vec<String> {
+    let mut names = match plan {
+        LogicalPlan::TableScan { table_name, .. } => match state.datasources.get(table_name) {
+            Some(table) if runs_python(table.as_ref()) => vec![table_name.clone()],
+            _ => vec![],
+        },
+        _ => vec![],
+    };
+    for input in utils::inputs(plan) {
+        for name in python_scans(input, state) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
@@ -1,9 +1,11 @@
 use pyo3::{basic::CompareOp, prelude::*, types::PyTuple, PyNumberProtocol, PyObjectProtocol};
 
-use datafusion::logical_plan::Expr as _Expr;
+use datafusion::logical_plan::{binary_expr, Expr as _Expr, Operator};
 use datafusion::physical_plan::udaf::AggregateUDF as _AggregateUDF;
 use datafusion::physical_plan::udf::ScalarUDF as _ScalarUDF;
 
+use crate::types::PyDataType;
+
 /// An expression that can be used on a DataFrame
 #[pyclass]
 #[derive(Debug, Clone)]
@@ -45,6 +47,12 @@ impl PyNumberProtocol for Expression {
         })
     }
 
+    fn __mod__(lhs: Expression, rhs: Expression) -> PyResult<Expression> {
+        Ok(Expression {
+            expr: binary_expr(lhs.expr, Operator::Modulus, rhs.expr),
+        })
+    }
+
     fn __and__(lhs: Expression, rhs: Expression) -> PyResult<Expression> {
         Ok(Expression {
             expr: lhs.expr.and(rhs.expr),
@@ -98,6 +106,42 @@ impl Expression {
             expr: self.expr.alias(name),
         })
     }
+
+    /// whether this expression is null
+    pub fn is_null(&self) -> PyResult<Expression> {
+        Ok(Expression {
+            expr: _Expr::IsNull(Box::new(self.expr.clone())),
+        })
+    }
+
+    /// whether this expression is not null
+    pub fn is_not_null(&self) -> PyResult<Expression> {
+        Ok(Expression {
+            expr: _Expr::IsNotNull(Box::new(self.expr.clone())),
+        })
+    }
+
+    /// casts this expression to the pyarrow type `to`
+    pub fn cast(&self, to: PyDataType) -> PyResult<Expression> {
+        Ok(Expression {
+            expr: _Expr::Cast {
+                expr: Box::new(self.expr.clone()),
+                data_type: to.data_type,
+            },
+        })
+    }
+
+    /// an expression to sort by this one, e.g. on `DataFrame.sort`
+    #[args(ascending = "true", nulls_first = "true")]
+    pub fn sort(&self, ascending: bool, nulls_first: bool) -> PyResult<Expression> {
+        Ok(Expression {
+            expr: _Expr::Sort {
+                expr: Box::new(self.expr.clone()),
+                asc: ascending,
+                nulls_first,
+            },
+        })
+    }
 }
 
 /// Represents a ScalarUDF
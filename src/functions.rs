@@ -5,6 +5,8 @@ use pyo3::{prelude::*, wrap_pyfunction};
 
 use datafusion::logical_plan;
 
+use crate::errors;
+use crate::scalar;
 use crate::udaf;
 use crate::udf;
 use crate::{expression, types::PyDataType};
@@ -18,15 +20,37 @@ fn col(name: &str) -> expression::Expression {
     };
 }
 
-/// Expression representing a constant value
+/// Expression representing a constant value: a bool, an int (an `int32` when it fits, `int64`
+/// otherwise), a float (`float64`), a str (`utf8`) or a pyarrow Scalar.
 #[pyfunction]
 #[text_signature = "(value)"]
-fn lit(value: i32) -> expression::Expression {
-    expression::Expression {
-        expr: logical_plan::lit(value),
+fn lit(value: &PyAny) -> PyResult<expression::Expression> {
+    Ok(expression::Expression {
+        expr: logical_plan::Expr::Literal(scalar::to_scalar(value)?),
+    })
+}
+
+/// Returns the name used to register a Python callable.
+/// Functions and classes have a `__qualname__`; other callables (e.g. an instance of a class
+/// implementing `__call__`) are named after their class.
+pub(crate) fn callable_name(fun: &PyObject, py: Python) -> PyResult<String> {
+    let fun = fun.as_ref(py);
+    if !fun.is_callable() {
+        return Err(errors::DataFusionError::Common(format!(
+            "The object \"{}\" is not callable",
+            fun.str()?
+        ))
+        .into());
+    }
+    match fun.getattr("__qualname__") {
+        Ok(name) => name.extract::<String>(),
+        Err(_) => fun.get_type().getattr("__qualname__")?.extract::<String>(),
     }
 }
 
+/// Creates a `ScalarUDF` from a Python callable.
+/// The callable is kept alive (and shared) for as long as the UDF is registered or referenced
+/// by a plan, i.e. the same object is called on every batch of every execution.
 pub(crate) fn create_udf(
     fun: PyObject,
     input_types: Vec<PyDataType>,
@@ -34,46 +58,59 @@ pub(crate) fn create_udf(
     name: &str,
 ) -> PyResult<expression::ScalarUDF> {
     let input_types: Vec<DataType> = input_types.iter().map(|d| d.data_type.clone()).collect();
+    let implementation = udf::array_udf(fun, name, return_type.data_type.clone());
     let return_type = Arc::new(return_type.data_type);
 
     Ok(expression::ScalarUDF {
-        function: logical_plan::create_udf(name, input_types, return_type, udf::array_udf(fun)),
+        function: logical_plan::create_udf(name, input_types, return_type, implementation),
     })
 }
 
-/// Creates a new udf.
-#[pyfunction]
-fn udf(
-    fun: PyObject,
-    input_types: Vec<PyDataType>,
-    return_type: PyDataType,
+/// Creates a `ScalarUDF` from a numpy universal function.
+/// When not provided, `input_types` defaults to one `float64` per input of the ufunc and
+/// `return_type` defaults to `float64`.
+pub(crate) fn create_ufunc(
+    ufunc: PyObject,
+    input_types: Option<Vec<PyDataType>>,
+    return_type: Option<PyDataType>,
+    name: &str,
     py: Python,
 ) -> PyResult<expression::ScalarUDF> {
-    let name = fun.getattr(py, "__qualname__")?.extract::<String>(py)?;
+    let input_types: Vec<DataType> = match input_types {
+        Some(input_types) => input_types.into_iter().map(|d| d.data_type).collect(),
+        None => {
+            let nin = ufunc.getattr(py, "nin")?.extract::<usize>(py)?;
+            vec![DataType::Float64; nin]
+        }
+    };
+    let return_type = return_type.map_or(DataType::Float64, |d| d.data_type);
 
-    create_udf(fun, input_types, return_type, &name)
+    Ok(expression::ScalarUDF {
+        function: logical_plan::create_udf(
+            name,
+            input_types,
+            Arc::new(return_type.clone()),
+            udf::numpy_udf(ufunc, return_type),
+        ),
+    })
 }
 
-/// Creates a new udf.
-#[pyfunction]
-fn udaf(
+/// Creates an `AggregateUDF` from a Python class (or any callable returning an accumulator)
+/// whose instances implement `update`, `merge`, `to_scalars` and `evaluate`.
+pub(crate) fn create_udaf(
     accumulator: PyObject,
     input_type: PyDataType,
     return_type: PyDataType,
     state_type: Vec<PyDataType>,
-    py: Python,
+    name: &str,
 ) -> PyResult<expression::AggregateUDF> {
-    let name = accumulator
-        .getattr(py, "__qualname__")?
-        .extract::<String>(py)?;
-
     let input_type = input_type.data_type;
     let return_type = Arc::new(return_type.data_type);
     let state_type = Arc::new(state_type.into_iter().map(|t| t.data_type).collect());
 
     Ok(expression::AggregateUDF {
         function: logical_plan::create_udaf(
-            &name,
+            name,
             input_type,
             return_type,
             udaf::array_udaf(accumulator),
@@ -82,6 +119,33 @@ fn udaf(
     })
 }
 
+/// Creates a new udf.
+#[pyfunction]
+fn udf(
+    fun: PyObject,
+    input_types: Vec<PyDataType>,
+    return_type: PyDataType,
+    py: Python,
+) -> PyResult<expression::ScalarUDF> {
+    let name = callable_name(&fun, py)?;
+
+    create_udf(fun, input_types, return_type, &name)
+}
+
+/// Creates a new udf.
+#[pyfunction]
+fn udaf(
+    accumulator: PyObject,
+    input_type: PyDataType,
+    return_type: PyDataType,
+    state_type: Vec<PyDataType>,
+    py: Python,
+) -> PyResult<expression::AggregateUDF> {
+    let name = callable_name(&accumulator, py)?;
+
+    create_udaf(accumulator, input_type, return_type, state_type, &name)
+}
+
 pub fn init(module: &PyModule) -> PyResult<()> {
     module.add_function(wrap_pyfunction!(col, module)?)?;
     module.add_function(wrap_pyfunction!(lit, module)?)?;
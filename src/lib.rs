@@ -1,27 +1,67 @@
-use pyo3::prelude::*;
+use pyo3::{prelude::*, wrap_pyfunction};
 
+mod admission;
+mod compute;
+mod config;
 mod context;
 mod dataframe;
+mod datasource;
+mod dbapi;
 mod errors;
+mod explain;
 mod expression;
 mod functions;
+mod logger;
+mod metrics;
+mod policy;
+mod provider;
+mod runtime;
+mod sample;
 mod scalar;
+mod stream;
+mod suggestions;
 mod to_py;
 mod to_rust;
 mod types;
 mod udaf;
 mod udf;
+mod unparser;
+mod variables;
+mod view;
+mod writer;
 
 /// DataFusion.
 #[pymodule]
 fn datafusion(py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<config::ExecutionConfig>()?;
     m.add_class::<context::ExecutionContext>()?;
+    m.add_class::<context::ContextSnapshot>()?;
     m.add_class::<dataframe::DataFrame>()?;
     m.add_class::<expression::Expression>()?;
+    m.add_class::<policy::SqlPolicy>()?;
+    m.add_class::<stream::RecordBatchStream>()?;
+
+    m.add(
+        "DataFusionError",
+        py.get_type::<errors::py::DataFusionError>(),
+    )?;
+    m.add("PlanError", py.get_type::<errors::py::PlanError>())?;
+    m.add(
+        "TableNotFoundError",
+        py.get_type::<errors::py::TableNotFoundError>(),
+    )?;
+    m.add("ParquetError", py.get_type::<errors::py::ParquetError>())?;
+
+    logger::init();
+    m.add_function(wrap_pyfunction!(logger::set_log_level, m)?)?;
 
     let functions = PyModule::new(py, "functions")?;
     functions::init(functions)?;
     m.add_submodule(functions)?;
 
+    let compute = PyModule::new(py, "compute")?;
+    compute::init(compute)?;
+    m.add_submodule(compute)?;
+
     Ok(())
 }
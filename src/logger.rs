@@ -0,0 +1,73 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use pyo3::prelude::*;
+
+use crate::errors;
+
+/// Forwards the records of Rust's `log` crate (used by DataFusion and Arrow) to Python's
+/// `logging` module, on the logger "datafusion".
+struct PythonLogger;
+
+impl Log for PythonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        // get GIL
+        let gil = pyo3::Python::acquire_gil();
+        let py = gil.python();
+
+        // the levels of Python's `logging`
+        let level = match record.level() {
+            Level::Error => 40,
+            Level::Warn => 30,
+            Level::Info => 20,
+            Level::Debug => 10,
+            Level::Trace => 5,
+        };
+        let message = format!("{}: {}", record.target(), record.args());
+
+        // logging must never fail the caller
+        let _ = py.import("logging").and_then(|logging| {
+            logging
+                .call1("getLogger", ("datafusion",))?
+                .call_method1("log", (level, message))
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+/// installs the logger, forwarding warnings and errors by default
+pub(crate) fn init() {
+    if log::set_boxed_logger(Box::new(PythonLogger)).is_ok() {
+        log::set_max_level(LevelFilter::Warn);
+    }
+}
+
+/// Sets the level of the records forwarded to the Python logger "datafusion".
+/// Valid levels are "OFF", "ERROR", "WARNING", "INFO", "DEBUG" and "TRACE".
+#[pyfunction]
+#[text_signature = "(level)"]
+pub(crate) fn set_log_level(level: &str) -> PyResult<()> {
+    let level = match level.to_uppercase().as_ref() {
+        "OFF" => LevelFilter::Off,
+        "ERROR" => LevelFilter::Error,
+        "WARNING" | "WARN" => LevelFilter::Warn,
+        "INFO" => LevelFilter::Info,
+        "DEBUG" => LevelFilter::Debug,
+        "TRACE" => LevelFilter::Trace,
+        other => {
+            return Err(errors::DataFusionError::Common(format!(
+                "The log level \"{}\" is not valid",
+                other
+            ))
+            .into())
+        }
+    };
+    log::set_max_level(level);
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use pyo3::{prelude::*, types::PyDict};
+
+use arrow::record_batch::RecordBatch;
+
+/// Statistics of a single execution. Memory and CPU usage are not measured by the engine.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct QueryStats {
+    /// time spent optimizing and planning
+    pub planning: Duration,
+    /// time spent executing
+    pub execution: Duration,
+    pub output_rows: u64,
+    pub output_batches: u64,
+    /// size of the buffers of the result
+    pub output_bytes: u64,
+    /// whether the result was truncated to the maximum number of rows of the DataFrame
+    pub truncated: bool,
+}
+
+impl QueryStats {
+    /// the statistics of an execution that returned `batches`
+    pub fn new(planning: Duration, execution: Duration, batches: &[RecordBatch]) -> Self {
+        Self {
+            planning,
+            execution,
+            output_rows: batches.iter().map(|b| b.num_rows() as u64).sum(),
+            output_batches: batches.len() as u64,
+            output_bytes: batches
+                .iter()
+                .flat_map(|b| b.columns())
+                .map(|array| array.get_buffer_memory_size() as u64)
+                .sum(),
+            truncated: false,
+        }
+    }
+
+    /// converts the statistics to a Python dictionary (durations in seconds)
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("planning_seconds", self.planning.as_secs_f64())?;
+        dict.set_item("execution_seconds", self.execution.as_secs_f64())?;
+        dict.set_item("output_rows", self.output_rows)?;
+        dict.set_item("output_batches", self.output_batches)?;
+        dict.set_item("output_bytes", self.output_bytes)?;
+        dict.set_item("truncated", self.truncated)?;
+        Ok(dict.to_object(py))
+    }
+}
+
+/// How an execution ended
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Outcome {
+    /// it returned `rows` rows in `batches` batches
+    Completed {
+        rows: u64,
+        batches: u64,
+    },
+    /// it was stopped before completing (e.g. an iterator of batches dropped before being
+    /// exhausted), after returning `rows` rows in `batches` batches
+    Cancelled {
+        rows: u64,
+        batches: u64,
+    },
+    Failed,
+}
+
+/// Cumulative metrics of the executions of the DataFrames of a context.
+/// Scans are not measured by the engine: rows scanned and bytes read are not available.
+#[derive(Debug, Default)]
+pub(crate) struct Metrics {
+    queries: AtomicU64,
+    failed_queries: AtomicU64,
+    cancelled_queries: AtomicU64,
+    output_rows: AtomicU64,
+    output_batches: AtomicU64,
+    execution_nanos: AtomicU64,
+}
+
+impl Metrics {
+    /// records the result of an execution that took `elapsed`: its batches, or `None` if it failed
+    pub fn record(&self, result: Option<&[RecordBatch]>, elapsed: Duration) {
+        let outcome = match result {
+            Some(batches) => Outcome::Completed {
+                rows: batches.iter().map(|b| b.num_rows() as u64).sum(),
+                batches: batches.len() as u64,
+            },
+            None => Outcome::Failed,
+        };
+        self.record_outcome(outcome, elapsed);
+    }
+
+    /// records how an execution that took `elapsed` ended
+    pub fn record_outcome(&self, outcome: Outcome, elapsed: Duration) {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        self.execution_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        match outcome {
+            Outcome::Completed { rows, batches } | Outcome::Cancelled { rows, batches } => {
+                self.output_rows.fetch_add(rows, Ordering::Relaxed);
+                self.output_batches.fetch_add(batches, Ordering::Relaxed);
+            }
+            Outcome::Failed => {
+                self.failed_queries.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        if let Outcome::Cancelled { .. } = outcome {
+            self.cancelled_queries.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// renders the metrics in Prometheus' text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let counters = [
+            (
+                "datafusion_queries_total",
+                "Number of executed queries.",
+                self.queries.load(Ordering::Relaxed) as f64,
+            ),
+            (
+                "datafusion_failed_queries_total",
+                "Number of executed queries that failed.",
+                self.failed_queries.load(Ordering::Relaxed) as f64,
+            ),
+            (
+                "datafusion_cancelled_queries_total",
+                "Number of executed queries that were stopped before completing.",
+                self.cancelled_queries.load(Ordering::Relaxed) as f64,
+            ),
+            (
+                "datafusion_output_rows_total",
+                "Number of rows returned by executed queries.",
+                self.output_rows.load(Ordering::Relaxed) as f64,
+            ),
+            (
+                "datafusion_output_batches_total",
+                "Number of record batches returned by executed queries.",
+                self.output_batches.load(Ordering::Relaxed) as f64,
+            ),
+            (
+                "datafusion_execution_seconds_total",
+                "Time spent executing queries.",
+                self.execution_nanos.load(Ordering::Relaxed) as f64 / 1e9,
+            ),
+        ];
+
+        let mut result = String::new();
+        for (name, help, value) in counters.iter() {
+            // writing to a String never fails
+            writeln!(result, "# HELP {} {}", name, help).unwrap();
+            writeln!(result, "# TYPE {} counter", name).unwrap();
+            writeln!(result, "{} {}", name, value).unwrap();
+        }
+        result
+    }
+}
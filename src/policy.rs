@@ -0,0 +1,114 @@
+use std::collections::HashSet;
+
+use pyo3::{exceptions::PyPermissionError, prelude::*};
+
+use datafusion::logical_plan::{Expr, LogicalPlan};
+use datafusion::optimizer::utils;
+
+use crate::errors;
+
+/// Restricts the tables and functions that a SQL statement may reference, e.g. to run the
+/// statements of different tenants on a shared context.
+/// Names are allowed when they are in the `allowed_*` lists (or these are `None`) and not in the
+/// `denied_*` lists. Function names are case-insensitive.
+#[pyclass]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SqlPolicy {
+    allowed_tables: Option<HashSet<String>>,
+    denied_tables: HashSet<String>,
+    allowed_functions: Option<HashSet<String>>,
+    denied_functions: HashSet<String>,
+}
+
+#[pymethods]
+impl SqlPolicy {
+    #[new]
+    #[args(
+        allowed_tables = "None",
+        denied_tables = "None",
+        allowed_functions = "None",
+        denied_functions = "None"
+    )]
+    fn new(
+        allowed_tables: Option<HashSet<String>>,
+        denied_tables: Option<HashSet<String>>,
+        allowed_functions: Option<HashSet<String>>,
+        denied_functions: Option<HashSet<String>>,
+    ) -> Self {
+        let lowercase = |names: HashSet<String>| names.iter().map(|n| n.to_lowercase()).collect();
+        Self {
+            allowed_tables,
+            denied_tables: denied_tables.unwrap_or_default(),
+            allowed_functions: allowed_functions.map(lowercase),
+            denied_functions: denied_functions.map(lowercase).unwrap_or_default(),
+        }
+    }
+}
+
+/// returns the name of the function called by `expr`, if any
+fn function_name(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::ScalarFunction { fun, .. } => Some(fun.to_string()),
+        Expr::ScalarUDF { fun, .. } => Some(fun.name.clone()),
+        Expr::AggregateFunction { fun, .. } => Some(fun.to_string()),
+        Expr::AggregateUDF { fun, .. } => Some(fun.name.clone()),
+        _ => None,
+    }
+}
+
+impl SqlPolicy {
+    /// whether `name` is in `allowed` (when given) and not in `denied`
+    fn is_allowed(allowed: &Option<HashSet<String>>, denied: &HashSet<String>, name: &str) -> bool {
+        allowed
+            .as_ref()
+            .map_or(true, |allowed| allowed.contains(name))
+            && !denied.contains(name)
+    }
+
+    fn check_expr(&self, expr: &Expr) -> PyResult<()> {
+        if let Some(name) = function_name(expr) {
+            let name = name.to_lowercase();
+            if !Self::is_allowed(&self.allowed_functions, &self.denied_functions, &name) {
+                return Err(PyPermissionError::new_err(format!(
+                    "The function \"{}\" is not allowed",
+                    name
+                )));
+            }
+        }
+        for expr in errors::wrap(utils::expr_sub_expressions(expr))? {
+            self.check_expr(&expr)?;
+        }
+        Ok(())
+    }
+
+    /// fails with a `PermissionError` if `plan` references a table or function that this policy
+    /// doesn't allow. `exempt_tables` are always allowed.
+    pub fn check(&self, plan: &LogicalPlan, exempt_tables: &[String]) -> PyResult<()> {
+        match plan {
+            LogicalPlan::TableScan { table_name, .. } => {
+                if !exempt_tables.contains(table_name)
+                    && !Self::is_allowed(&self.allowed_tables, &self.denied_tables, table_name)
+                {
+                    return Err(PyPermissionError::new_err(format!(
+                        "The table \"{}\" is not allowed",
+                        table_name
+                    )));
+                }
+            }
+            LogicalPlan::CreateExternalTable { name, .. } => {
+                return Err(PyPermissionError::new_err(format!(
+                    "The table \"{}\" can't be registered by a statement with a policy",
+                    name
+                )));
+            }
+            _ => {}
+        }
+        for expr in utils::expressions(plan) {
+            self.check_expr(&expr)?;
+        }
+        for input in utils::inputs(plan) {
+            self.check(input, exempt_tables)?;
+        }
+        Ok(())
+    }
+}
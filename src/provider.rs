@@ -0,0 +1,132 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use pyo3::{prelude::*, types::PyList};
+
+use arrow::datatypes::{Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use datafusion::datasource::TableProvider;
+use datafusion::error::Result;
+use datafusion::physical_plan::{memory::MemoryExec, ExecutionPlan};
+
+use crate::errors;
+use crate::to_rust::to_rust_batch;
+use crate::types::PyDataType;
+
+/// A table whose rows are provided by a Python object implementing `schema()`, returning a
+/// pyarrow Schema, and `scan(projection, filters)`, returning an iterable of pyarrow
+/// RecordBatches. `projection` is the list of the names of the columns used by the plan (or
+/// `None` for all of them) and `filters` is always empty: DataFusion doesn't push filters into
+/// tables yet and applies them to the scanned rows. Exceptions of `scan` are raised as they are.
+pub(crate) struct PyTableProvider {
+    provider: PyObject,
+    schema: SchemaRef,
+}
+
+/// converts a pyarrow Schema into a Rust schema
+fn to_rust_schema(schema: &PyAny) -> PyResult<Schema> {
+    let fields = schema
+        .iter()?
+        .map(|field| {
+            let field = field?;
+            let name = field.getattr("name")?.extract::<String>()?;
+            let nullable = field.getattr("nullable")?.extract::<bool>()?;
+            let py_data_type = field.getattr("type")?;
+            let data_type = py_data_type.extract::<PyDataType>().map_err(|_| {
+                errors::DataFusionError::Common(format!(
+                    "The column \"{}\" has the type {}, which is not supported yet",
+                    name, py_data_type
+                ))
+            })?;
+            Ok(Field::new(&name, data_type.data_type, nullable))
+        })
+        .collect::<PyResult<_>>()?;
+    Ok(Schema::new(fields))
+}
+
+impl PyTableProvider {
+    /// Creates a new table from `provider`, whose schema is read once.
+    pub fn try_new(provider: PyObject, py: Python) -> PyResult<Self> {
+        let schema = to_rust_schema(provider.call_method0(py, "schema")?.as_ref(py))?;
+        Ok(Self {
+            provider,
+            schema: Arc::new(schema),
+        })
+    }
+
+    /// scans the provider, returning batches with the columns in `projection`. Providers may
+    /// ignore the projection: the columns are selected by name from the batches they return.
+    fn fetch(
+        &self,
+        projection: &Option<Vec<usize>>,
+        py: Python,
+    ) -> PyResult<(SchemaRef, Vec<RecordBatch>)> {
+        let (fields, names) = match projection {
+            Some(projection) => {
+                let fields = projection
+                    .iter()
+                    .map(|i| self.schema.field(*i).clone())
+                    .collect::<Vec<_>>();
+                let names = fields
+                    .iter()
+                    .map(|field| field.name().clone())
+                    .collect::<Vec<_>>();
+                (fields, names.to_object(py))
+            }
+            None => (self.schema.fields().clone(), py.None()),
+        };
+        let schema = Arc::new(Schema::new(fields));
+
+        let batches = self
+            .provider
+            .call_method1(py, "scan", (names, PyList::empty(py)))?;
+        let batches = batches
+            .as_ref(py)
+            .iter()?
+            .map(|batch| {
+                let batch = to_rust_batch(batch?)?;
+                let columns = schema
+                    .fields()
+                    .iter()
+                    .map(|field| {
+                        let i = batch.schema().index_of(field.name()).map_err(|_| {
+                            errors::DataFusionError::Common(format!(
+                                "The batches scanned from the provider have no column \"{}\"",
+                                field.name()
+                            ))
+                        })?;
+                        Ok(batch.column(i).clone())
+                    })
+                    .collect::<PyResult<_>>()?;
+                Ok(RecordBatch::try_new(schema.clone(), columns)
+                    .map_err(errors::DataFusionError::from)?)
+            })
+            .collect::<PyResult<_>>()?;
+        Ok((schema, batches))
+    }
+}
+
+impl TableProvider for PyTableProvider {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn scan(
+        &self,
+        projection: &Option<Vec<usize>>,
+        _batch_size: usize,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        // get GIL
+        let gil = pyo3::Python::acquire_gil();
+        let py = gil.python();
+
+        let (schema, batches) = self
+            .fetch(projection, py)
+            .map_err(|e| errors::python_error(e, py))?;
+        Ok(Arc::new(MemoryExec::try_new(&[batches], schema, None)?))
+    }
+}
@@ -0,0 +1,78 @@
+use arrow::datatypes::{DataType, Schema};
+use datafusion::logical_plan::LogicalPlan;
+use datafusion::optimizer::utils;
+use tokio::runtime::{Builder, Runtime};
+
+use crate::errors::DataFusionError;
+
+/// Settings of the execution of plans: of the tokio runtime that executes them and of their
+/// batches. Unset values use tokio's defaults (one worker thread per core and up to 512 threads)
+/// and the batch size of the context.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct RuntimeConfig {
+    /// number of threads executing plans
+    pub worker_threads: Option<usize>,
+    /// maximum number of threads, including `worker_threads` and threads used for blocking IO
+    pub max_threads: Option<usize>,
+    /// the (estimated) size in bytes of the batches read from tables, see `batch_size_of`
+    pub target_batch_bytes: Option<usize>,
+}
+
+impl RuntimeConfig {
+    /// creates a new runtime with these settings
+    pub fn build(&self) -> Result<Runtime, DataFusionError> {
+        let mut builder = Builder::new();
+        builder.threaded_scheduler().enable_all();
+        if let Some(worker_threads) = self.worker_threads {
+            builder.core_threads(worker_threads);
+        }
+        if let Some(max_threads) = self.max_threads {
+            builder.max_threads(max_threads);
+        }
+        builder
+            .build()
+            .map_err(|e| DataFusionError::Common(format!("Unable to start the runtime: {}", e)))
+    }
+}
+
+/// the estimated size in bytes of a value of `data_type`; variable-sized types are assumed to
+/// hold short values
+fn value_width(data_type: &DataType) -> usize {
+    match data_type {
+        DataType::Boolean | DataType::Int8 | DataType::UInt8 => 1,
+        DataType::Int16 | DataType::UInt16 | DataType::Float16 => 2,
+        DataType::Int32 | DataType::UInt32 | DataType::Float32 => 4,
+        DataType::Int64 | DataType::UInt64 | DataType::Float64 => 8,
+        DataType::Utf8 | DataType::Binary => 4 + 32,
+        DataType::LargeUtf8 | DataType::LargeBinary => 8 + 32,
+        _ => 16,
+    }
+}
+
+/// the estimated size in bytes of a row of `schema`
+fn row_width(schema: &Schema) -> usize {
+    schema
+        .fields()
+        .iter()
+        .map(|field| value_width(field.data_type()))
+        .sum()
+}
+
+/// the widest row (in bytes) of the tables scanned by `plan`
+fn widest_scan(plan: &LogicalPlan) -> usize {
+    let width = match plan {
+        LogicalPlan::TableScan { .. } => row_width(plan.schema()),
+        _ => 0,
+    };
+    utils::inputs(plan)
+        .into_iter()
+        .map(widest_scan)
+        .fold(width, usize::max)
+}
+
+/// the number of rows of the batches read by `plan` (an optimized plan, whose scans only read the
+/// columns it uses) so that each has about `target_bytes`: wide tables are read in fewer rows
+/// per batch than narrow ones
+pub(crate) fn batch_size_of(plan: &LogicalPlan, target_bytes: usize) -> usize {
+    (target_bytes / widest_scan(plan).max(1)).max(1)
+}
@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use rand::Rng;
+
+use arrow::array::{ArrayRef, BooleanArray};
+use arrow::datatypes::DataType;
+use datafusion::error::ExecutionError;
+use datafusion::logical_plan::{col, create_udf, Expr, LogicalPlan, LogicalPlanBuilder};
+use datafusion::optimizer::utils;
+
+use crate::errors::{self, DataFusionError};
+
+/// the name of the sampling function, which can't collide with the name of a registered UDF
+/// because SQL identifiers can't start with `#`
+const SAMPLE_FUNCTION: &str = "#datafusion_sample";
+
+/// a predicate that is true for each row with probability `fraction`, evaluated on the column
+/// `column` of type `data_type` (only used for the number of rows)
+fn sample_predicate(column: &str, data_type: &DataType, fraction: f64) -> Expr {
+    let implementation = Arc::new(
+        move |args: &[ArrayRef]| -> Result<ArrayRef, ExecutionError> {
+            let mut rng = rand::thread_rng();
+            let sampled = (0..args[0].len())
+                .map(|_| rng.gen::<f64>() < fraction)
+                .collect::<Vec<_>>();
+            Ok(Arc::new(BooleanArray::from(sampled)))
+        },
+    );
+    create_udf(
+        SAMPLE_FUNCTION,
+        vec![data_type.clone()],
+        Arc::new(DataType::Boolean),
+        implementation,
+    )
+    .call(vec![col(column)])
+}
+
+/// `plan` reading a random sample of about `fraction` of the rows of each of the tables that it
+/// scans. Rows are sampled as they are read: every file is still read in full, but the
+/// operators above the scans (e.g. joins and aggregations) only process the sampled rows.
+pub(crate) fn sample(plan: &LogicalPlan, fraction: f64) -> Result<LogicalPlan, DataFusionError> {
+    if !(fraction > 0.0 && fraction <= 1.0) {
+        return Err(DataFusionError::Common(format!(
+            "The sampled fraction must be in (0, 1], got {}",
+            fraction
+        )));
+    }
+    sample_scans(plan, fraction)
+}
+
+fn sample_scans(plan: &LogicalPlan, fraction: f64) -> Result<LogicalPlan, DataFusionError> {
+    match plan {
+        LogicalPlan::TableScan { table_name, .. } => {
+            let field = plan.schema().fields().first().cloned().ok_or_else(|| {
+                DataFusionError::Common(format!(
+                    "The table \"{}\" has no columns to sample",
+                    table_name
+                ))
+            })?;
+            let predicate = sample_predicate(field.name(), field.data_type(), fraction);
+            Ok(errors::wrap(
+                LogicalPlanBuilder::from(plan)
+                    .filter(predicate)
+                    .and_then(|builder| builder.build()),
+            )?)
+        }
+        _ => {
+            let inputs = utils::inputs(plan)
+                .into_iter()
+                .map(|input| sample_scans(input, fraction))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(errors::wrap(utils::from_plan(
+                plan,
+                &utils::expressions(plan),
+                &inputs,
+            ))?)
+        }
+    }
+}
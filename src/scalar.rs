@@ -17,3 +17,21 @@ impl<'source> FromPyObject<'source> for Scalar {
         })
     }
 }
+
+/// converts a Python value to a scalar: a bool, an int (an `int32` when it fits, `int64`
+/// otherwise), a float (`float64`), a str (`utf8`) or a pyarrow Scalar
+pub(crate) fn to_scalar(value: &PyAny) -> PyResult<_Scalar> {
+    Ok(if let Ok(value) = value.extract::<bool>() {
+        _Scalar::Boolean(Some(value))
+    } else if let Ok(value) = value.extract::<i32>() {
+        _Scalar::Int32(Some(value))
+    } else if let Ok(value) = value.extract::<i64>() {
+        _Scalar::Int64(Some(value))
+    } else if let Ok(value) = value.extract::<f64>() {
+        _Scalar::Float64(Some(value))
+    } else if let Ok(value) = value.extract::<String>() {
+        _Scalar::Utf8(Some(value))
+    } else {
+        value.extract::<Scalar>()?.scalar
+    })
+}
@@ -0,0 +1,151 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use pyo3::{prelude::*, PyIterProtocol};
+use tokio::runtime::Runtime;
+
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
+use datafusion::physical_plan::{ExecutionPlan, SendableRecordBatchStream};
+
+use crate::admission::Permit;
+use crate::errors;
+use crate::metrics::{Metrics, Outcome};
+use crate::to_py;
+
+/// An iterator of the `RecordBatch`es of the result of a plan, returned by
+/// `DataFrame.iter_batches` and `ExecutionContext.sql_iter`.
+/// Partitions are executed one after the other and each batch is only computed when requested.
+/// The execution holds its slot of the context's `max_concurrent_queries` until the iterator is
+/// exhausted or dropped.
+#[pyclass(unsendable)]
+pub(crate) struct RecordBatchStream {
+    plan: Arc<dyn ExecutionPlan>,
+    /// the next partition to execute
+    partition: usize,
+    /// the stream of the partition being executed
+    stream: Option<SendableRecordBatchStream>,
+    /// declared after `stream`, which is dropped before the runtime that drives it
+    runtime: Runtime,
+    metrics: Arc<Metrics>,
+    /// released (and `None`) once the execution is finished
+    permit: Option<Permit>,
+    elapsed: Duration,
+    rows: u64,
+    batches: u64,
+}
+
+impl RecordBatchStream {
+    pub fn new(
+        runtime: Runtime,
+        plan: Arc<dyn ExecutionPlan>,
+        metrics: Arc<Metrics>,
+        permit: Permit,
+    ) -> Self {
+        Self {
+            plan,
+            partition: 0,
+            stream: None,
+            runtime,
+            metrics,
+            permit: Some(permit),
+            elapsed: Duration::default(),
+            rows: 0,
+            batches: 0,
+        }
+    }
+
+    /// the schema of the batches
+    pub fn schema(&self) -> SchemaRef {
+        self.plan.schema()
+    }
+
+    /// computes the next batch of the result, executing the next partitions as needed
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>, errors::DataFusionError> {
+        let partitions = self.plan.output_partitioning().partition_count();
+        loop {
+            if self.stream.is_none() {
+                if self.partition == partitions {
+                    return Ok(None);
+                }
+                let plan = self.plan.clone();
+                let partition = self.partition;
+                self.stream = Some(self.runtime.block_on(plan.execute(partition))?);
+                self.partition += 1;
+            }
+            let stream = self.stream.as_mut().unwrap();
+            match self.runtime.block_on(stream.next()) {
+                Some(batch) => return Ok(Some(batch?)),
+                None => self.stream = None,
+            }
+        }
+    }
+
+    /// records the execution on the context's metrics and releases its slot
+    fn finish(&mut self, outcome: Outcome) {
+        if self.permit.take().is_some() {
+            self.metrics.record_outcome(outcome, self.elapsed);
+        }
+    }
+}
+
+/// the batches of the result, computed one at a time
+impl Iterator for RecordBatchStream {
+    type Item = Result<RecordBatch, errors::DataFusionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.permit.is_none() {
+            return None;
+        }
+        let start = Instant::now();
+        let batch = self.next_batch();
+        self.elapsed += start.elapsed();
+        match batch {
+            Ok(Some(batch)) => {
+                self.rows += batch.num_rows() as u64;
+                self.batches += 1;
+                Some(Ok(batch))
+            }
+            Ok(None) => {
+                let outcome = Outcome::Completed {
+                    rows: self.rows,
+                    batches: self.batches,
+                };
+                self.finish(outcome);
+                None
+            }
+            Err(e) => {
+                self.finish(Outcome::Failed);
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[pyproto]
+impl PyIterProtocol for RecordBatchStream {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        let py = slf.py();
+        let stream: &mut RecordBatchStream = &mut *slf;
+        match py.allow_threads(move || stream.next()) {
+            Some(batch) => Ok(Some(to_py::to_py_record_batch(&batch?, py)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Drop for RecordBatchStream {
+    fn drop(&mut self) {
+        // iterators dropped before being exhausted count as cancelled executions
+        let outcome = Outcome::Cancelled {
+            rows: self.rows,
+            batches: self.batches,
+        };
+        self.finish(outcome);
+    }
+}
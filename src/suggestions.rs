@@ -0,0 +1,85 @@
+use std::collections::BTreeSet;
+
+use datafusion::error::ExecutionError;
+use datafusion::execution::context::ExecutionContextState;
+
+use crate::errors::DataFusionError;
+
+/// the number of single-character insertions, deletions or substitutions that turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + if a == *b { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// the name in `names` closest to `name`, if any is close enough to be a typo of it
+fn closest<'a>(name: &str, names: &'a BTreeSet<String>) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    names
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+/// the identifier-like words of `text`
+fn words(text: &str) -> BTreeSet<&str> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// the names of the tables registered in `state` and of their columns
+fn catalog_names(state: &ExecutionContextState) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    for (name, table) in state.datasources.iter() {
+        names.insert(name.clone());
+        for field in table.schema().fields() {
+            names.insert(field.name().clone());
+        }
+    }
+    names
+}
+
+/// the error of planning `query` against `state`, to which a suggestion is added for each name
+/// of `query` that the error mentions and that is a typo of the name of a table or column of
+/// `state`, e.g. `Did you mean "price"?` for a column `prices`. Planning errors are `Plan`
+/// errors.
+pub(crate) fn with_suggestions(
+    error: ExecutionError,
+    query: &str,
+    state: &ExecutionContextState,
+) -> DataFusionError {
+    // e.g. unsupported statements or files that can't be read by `CREATE EXTERNAL TABLE`
+    if let ExecutionError::NotImplemented(_)
+    | ExecutionError::IoError(_)
+    | ExecutionError::ParquetError(_)
+    | ExecutionError::ArrowError(_) = error
+    {
+        return error.into();
+    }
+    let message = DataFusionError::from(error).to_string();
+    let names = catalog_names(state);
+    let query_words = words(query);
+
+    let suggestions = words(&message)
+        .intersection(&query_words)
+        .filter(|word| !names.contains(**word))
+        .filter_map(|word| closest(word, &names))
+        .map(|name| format!("Did you mean \"{}\"?", name))
+        .collect::<Vec<_>>();
+    if suggestions.is_empty() {
+        DataFusionError::Plan(message)
+    } else {
+        DataFusionError::Plan(format!("{} {}", message, suggestions.join(" ")))
+    }
+}
@@ -1,12 +1,18 @@
 use pyo3::prelude::*;
-use pyo3::{libc::uintptr_t, PyErr};
+use pyo3::{
+    libc::uintptr_t,
+    types::{PyDict, PyType},
+    PyErr,
+};
 
 use std::convert::From;
 
 use arrow::array::ArrayRef;
+use arrow::datatypes::Schema;
 use arrow::record_batch::RecordBatch;
 
 use crate::errors;
+use crate::types::to_py_data_type;
 
 pub fn to_py_array(array: &ArrayRef, py: Python) -> PyResult<PyObject> {
     let (array_pointer, schema_pointer) = array
@@ -22,8 +28,40 @@ pub fn to_py_array(array: &ArrayRef, py: Python) -> PyResult<PyObject> {
     Ok(array.to_object(py))
 }
 
+/// returns the pyarrow types that the columns of `schema` are converted to, from `converters`,
+/// a dictionary of column names to pyarrow types or to names of numpy dtypes (e.g.
+/// `"datetime64[ms]"` or `"bytes"`)
+fn converter_types<'a>(
+    schema: &Schema,
+    converters: &'a PyDict,
+    py: Python<'a>,
+) -> PyResult<Vec<Option<&'a PyAny>>> {
+    let pyarrow = py.import("pyarrow")?;
+    let data_type = pyarrow.getattr("DataType")?.downcast::<PyType>()?;
+    let numpy = py.import("numpy")?;
+
+    let mut types = vec![None; schema.fields().len()];
+    for (name, target) in converters.iter() {
+        let name = name.extract::<String>()?;
+        let index = schema.index_of(&name).map_err(|_| {
+            errors::DataFusionError::Common(format!(
+                "The result has no column \"{}\" to convert",
+                name
+            ))
+        })?;
+        types[index] = Some(if data_type.is_instance(target)? {
+            target
+        } else {
+            let dtype = numpy.call1("dtype", (target,))?;
+            pyarrow.call1("from_numpy_dtype", (dtype,))?
+        });
+    }
+    Ok(types)
+}
+
 fn to_py_batch<'a>(
     batch: &RecordBatch,
+    types: &[Option<&PyAny>],
     py: Python,
     pyarrow: &'a PyModule,
 ) -> Result<PyObject, PyErr> {
@@ -31,8 +69,16 @@ fn to_py_batch<'a>(
     let mut py_names = vec![];
 
     let schema = batch.schema();
-    for (array, field) in batch.columns().iter().zip(schema.fields().iter()) {
-        let array = to_py_array(array, py)?;
+    for ((array, field), data_type) in batch
+        .columns()
+        .iter()
+        .zip(schema.fields().iter())
+        .zip(types.iter())
+    {
+        let mut array = to_py_array(array, py)?;
+        if let Some(data_type) = data_type {
+            array = array.call_method1(py, "cast", (*data_type,))?;
+        }
 
         py_arrays.push(array);
         py_names.push(field.name());
@@ -45,17 +91,63 @@ fn to_py_batch<'a>(
     Ok(PyObject::from(record))
 }
 
-/// Converts a Vec<RecordBatch> into a Vec<RecordBatch> represented in PyArrow
-pub fn to_py(batches: &Vec<RecordBatch>) -> PyResult<PyObject> {
-    let gil = pyo3::Python::acquire_gil();
-    let py = gil.python();
+/// Converts a Vec<RecordBatch> into a Vec<RecordBatch> represented in PyArrow.
+/// The columns named in `converters` are casted to the type they map to: a pyarrow type or the
+/// name of a numpy dtype (e.g. `"datetime64[ms]"` or `"bytes"`).
+pub fn to_py(
+    schema: &Schema,
+    batches: &Vec<RecordBatch>,
+    converters: Option<&PyDict>,
+    py: Python,
+) -> PyResult<PyObject> {
     let pyarrow = PyModule::import(py, "pyarrow")?;
     let builtins = PyModule::import(py, "builtins")?;
 
+    let types = match converters {
+        Some(converters) => converter_types(schema, converters, py)?,
+        None => vec![None; schema.fields().len()],
+    };
+
     let mut py_batches = vec![];
     for batch in batches {
-        py_batches.push(to_py_batch(batch, py, pyarrow)?);
+        py_batches.push(to_py_batch(batch, &types, py, pyarrow)?);
     }
     let result = builtins.call1("list", (py_batches,))?;
     Ok(PyObject::from(result))
 }
+
+/// Converts a RecordBatch into a pyarrow RecordBatch
+pub fn to_py_record_batch(batch: &RecordBatch, py: Python) -> PyResult<PyObject> {
+    let pyarrow = PyModule::import(py, "pyarrow")?;
+
+    let types = vec![None; batch.num_columns()];
+    to_py_batch(batch, &types, py, pyarrow)
+}
+
+/// Converts a Vec<RecordBatch> into a pyarrow Table.
+/// The schema of empty results is converted from `schema`, which supports fewer types than
+/// the batches themselves.
+pub fn to_py_table(schema: &Schema, batches: &Vec<RecordBatch>, py: Python) -> PyResult<PyObject> {
+    let py_batches = to_py(schema, batches, None, py)?;
+
+    let pyarrow = PyModule::import(py, "pyarrow")?;
+    let table = pyarrow.getattr("Table")?;
+
+    let table = if batches.is_empty() {
+        let fields = schema
+            .fields()
+            .iter()
+            .map(|field| {
+                Ok((
+                    field.name().as_str(),
+                    to_py_data_type(field.data_type(), py)?,
+                ))
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        let py_schema = pyarrow.call1("schema", (fields,))?;
+        table.call_method1("from_batches", (py_batches, py_schema))?
+    } else {
+        table.call_method1("from_batches", (py_batches,))?
+    };
+    Ok(PyObject::from(table))
+}
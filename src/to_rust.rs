@@ -7,8 +7,9 @@ use arrow::{
     ffi,
     record_batch::RecordBatch,
 };
+use datafusion::datasource::MemTable;
 use datafusion::scalar::ScalarValue;
-use pyo3::{libc::uintptr_t, prelude::*};
+use pyo3::{libc::uintptr_t, prelude::*, types::PyType};
 
 use crate::{errors, types::PyDataType};
 
@@ -30,36 +31,84 @@ pub fn to_rust(ob: &PyAny) -> PyResult<ArrayRef> {
     Ok(array)
 }
 
+/// decodes `array` if it is dictionary-encoded (e.g. from a pandas Categorical), as dictionaries
+/// are not supported by the engine yet
+fn decode_dictionary(array: &PyAny) -> PyResult<&PyAny> {
+    let is_dictionary = array
+        .py()
+        .import("pyarrow.types")?
+        .call1("is_dictionary", (array.getattr("type")?,))?
+        .extract::<bool>()?;
+    if is_dictionary {
+        array.call_method0("dictionary_decode")
+    } else {
+        Ok(array)
+    }
+}
+
+/// converts a pyarrow RecordBatch into a Rust RecordBatch.
+/// Dictionary-encoded columns are decoded.
 pub fn to_rust_batch(batch: &PyAny) -> PyResult<RecordBatch> {
     let schema = batch.getattr("schema")?;
     let names = schema.getattr("names")?.extract::<Vec<String>>()?;
 
+    let columns = (0..names.len())
+        .map(|i| decode_dictionary(batch.call_method1("column", (i,))?))
+        .collect::<PyResult<Vec<_>>>()?;
+
     let fields = names
         .iter()
+        .zip(columns.iter())
         .enumerate()
-        .map(|(i, name)| {
+        .map(|(i, (name, column))| {
             let field = schema.call_method1("field", (i,))?;
             let nullable = field.getattr("nullable")?.extract::<bool>()?;
-            let py_data_type = field.getattr("type")?;
-            let data_type = py_data_type.extract::<PyDataType>()?.data_type;
+            let py_data_type = column.getattr("type")?;
+            let data_type = py_data_type
+                .extract::<PyDataType>()
+                .map_err(|_| {
+                    errors::DataFusionError::Common(format!(
+                        "The column \"{}\" has the type {}, which is not supported yet",
+                        name, py_data_type
+                    ))
+                })?
+                .data_type;
             Ok(Field::new(name, data_type, nullable))
         })
         .collect::<PyResult<_>>()?;
 
     let schema = Arc::new(Schema::new(fields));
 
-    let arrays = (0..names.len())
-        .map(|i| {
-            let array = batch.call_method1("column", (i,))?;
-            to_rust(array)
-        })
-        .collect::<PyResult<_>>()?;
+    let arrays = columns.into_iter().map(to_rust).collect::<PyResult<_>>()?;
 
     let batch =
         RecordBatch::try_new(schema, arrays).map_err(|e| errors::DataFusionError::from(e))?;
     Ok(batch)
 }
 
+/// converts a sequence of values (e.g. a list or a pyarrow Array) into a table with a single
+/// column named `value`
+pub fn to_rust_values_table(values: &PyAny, py: Python) -> PyResult<MemTable> {
+    let pyarrow = py.import("pyarrow")?;
+    let array_type = pyarrow.getattr("Array")?.downcast::<PyType>()?;
+    let values = if array_type.is_instance(values)? {
+        values
+    } else {
+        pyarrow.call1("array", (values,))?
+    };
+    let array = to_rust(decode_dictionary(values)?)?;
+
+    let schema = Arc::new(Schema::new(vec![Field::new(
+        "value",
+        array.data_type().clone(),
+        true,
+    )]));
+    let batch = RecordBatch::try_new(schema.clone(), vec![array])
+        .map_err(|e| errors::DataFusionError::from(e))?;
+
+    Ok(errors::wrap(MemTable::new(schema, vec![vec![batch]]))?)
+}
+
 /// converts a pyarrow Scalar into a Rust Scalar
 pub fn to_rust_scalar(ob: &PyAny) -> PyResult<ScalarValue> {
     let t = ob
@@ -67,28 +116,31 @@ pub fn to_rust_scalar(ob: &PyAny) -> PyResult<ScalarValue> {
         .getattr("__name__")?
         .extract::<&str>()?;
 
+    // `None` for null scalars
     let p = ob.call_method0("as_py")?;
 
     Ok(match t {
-        "Int8Scalar" => ScalarValue::Int8(Some(p.extract::<i8>()?)),
-        "Int16Scalar" => ScalarValue::Int16(Some(p.extract::<i16>()?)),
-        "Int32Scalar" => ScalarValue::Int32(Some(p.extract::<i32>()?)),
-        "Int64Scalar" => ScalarValue::Int64(Some(p.extract::<i64>()?)),
-        "UInt8Scalar" => ScalarValue::UInt8(Some(p.extract::<u8>()?)),
-        "UInt16Scalar" => ScalarValue::UInt16(Some(p.extract::<u16>()?)),
-        "UInt32Scalar" => ScalarValue::UInt32(Some(p.extract::<u32>()?)),
-        "UInt64Scalar" => ScalarValue::UInt64(Some(p.extract::<u64>()?)),
-        "FloatScalar" => ScalarValue::Float32(Some(p.extract::<f32>()?)),
-        "DoubleScalar" => ScalarValue::Float64(Some(p.extract::<f64>()?)),
-        "BooleanScalar" => ScalarValue::Boolean(Some(p.extract::<bool>()?)),
-        "StringScalar" => ScalarValue::Utf8(Some(p.extract::<String>()?)),
-        "LargeStringScalar" => ScalarValue::LargeUtf8(Some(p.extract::<String>()?)),
+        "Int8Scalar" => ScalarValue::Int8(p.extract::<Option<i8>>()?),
+        "Int16Scalar" => ScalarValue::Int16(p.extract::<Option<i16>>()?),
+        "Int32Scalar" => ScalarValue::Int32(p.extract::<Option<i32>>()?),
+        "Int64Scalar" => ScalarValue::Int64(p.extract::<Option<i64>>()?),
+        "UInt8Scalar" => ScalarValue::UInt8(p.extract::<Option<u8>>()?),
+        "UInt16Scalar" => ScalarValue::UInt16(p.extract::<Option<u16>>()?),
+        "UInt32Scalar" => ScalarValue::UInt32(p.extract::<Option<u32>>()?),
+        "UInt64Scalar" => ScalarValue::UInt64(p.extract::<Option<u64>>()?),
+        "FloatScalar" => ScalarValue::Float32(p.extract::<Option<f32>>()?),
+        "DoubleScalar" => ScalarValue::Float64(p.extract::<Option<f64>>()?),
+        "BooleanScalar" => ScalarValue::Boolean(p.extract::<Option<bool>>()?),
+        "StringScalar" => ScalarValue::Utf8(p.extract::<Option<String>>()?),
+        "LargeStringScalar" => ScalarValue::LargeUtf8(p.extract::<Option<String>>()?),
         other => {
+            let data_type = ob.getattr("type")?;
             return Err(errors::DataFusionError::Common(format!(
-                "Type \"{}\"not yet implemented",
-                other
+                "Scalars of type {} ({}) can't be converted yet; supported types are booleans, \
+                 integers, floats and strings",
+                data_type, other
             ))
-            .into())
+            .into());
         }
     })
 }
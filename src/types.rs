@@ -1,5 +1,5 @@
 use arrow::datatypes::DataType;
-use pyo3::{FromPyObject, PyAny, PyResult};
+use pyo3::{prelude::*, FromPyObject, PyAny, PyResult};
 
 use crate::errors;
 
@@ -12,7 +12,9 @@ pub struct PyDataType {
 impl<'source> FromPyObject<'source> for PyDataType {
     fn extract(ob: &'source PyAny) -> PyResult<Self> {
         let id = ob.getattr("id")?.extract::<i32>()?;
-        let data_type = data_type_id(&id)?;
+        let data_type = data_type_id(&id).map_err(|_| {
+            errors::DataFusionError::Common(format!("The pyarrow type {} is not supported yet", ob))
+        })?;
         Ok(PyDataType { data_type })
     }
 }
@@ -57,3 +59,34 @@ fn data_type_id(id: &i32) -> Result<DataType, errors::DataFusionError> {
         }
     })
 }
+
+/// converts a native DataType into the corresponding pyarrow DataType
+pub fn to_py_data_type(data_type: &DataType, py: Python) -> PyResult<PyObject> {
+    let constructor = match data_type {
+        DataType::Null => "null",
+        DataType::Boolean => "bool_",
+        DataType::UInt8 => "uint8",
+        DataType::Int8 => "int8",
+        DataType::UInt16 => "uint16",
+        DataType::Int16 => "int16",
+        DataType::UInt32 => "uint32",
+        DataType::Int32 => "int32",
+        DataType::UInt64 => "uint64",
+        DataType::Int64 => "int64",
+        DataType::Float16 => "float16",
+        DataType::Float32 => "float32",
+        DataType::Float64 => "float64",
+        DataType::Binary => "binary",
+        DataType::Utf8 => "utf8",
+        DataType::LargeBinary => "large_binary",
+        DataType::LargeUtf8 => "large_utf8",
+        other => {
+            return Err(errors::DataFusionError::Common(format!(
+                "The type {:?} is not yet supported in Python",
+                other
+            ))
+            .into())
+        }
+    };
+    Ok(py.import("pyarrow")?.call0(constructor)?.to_object(py))
+}
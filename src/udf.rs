@@ -1,8 +1,11 @@
 use std::sync::Arc;
 
-use pyo3::{prelude::*, types::PyTuple};
+use pyo3::{
+    prelude::*,
+    types::{PyDict, PyTuple},
+};
 
-use arrow::array;
+use arrow::{array, compute, datatypes::DataType};
 
 use datafusion::error::ExecutionError;
 use datafusion::physical_plan::functions::ScalarFunctionImplementation;
@@ -12,13 +15,23 @@ use crate::to_rust::to_rust;
 
 /// creates a DataFusion's UDF implementation from a python function that expects pyarrow arrays
 /// This is more efficient as it performs a zero-copy of the contents.
-pub fn array_udf(func: PyObject) -> ScalarFunctionImplementation {
+/// `name` and `return_type` are used to validate the result and to describe errors.
+pub fn array_udf(
+    func: PyObject,
+    name: &str,
+    return_type: DataType,
+) -> ScalarFunctionImplementation {
+    let name = name.to_owned();
     Arc::new(
         move |args: &[array::ArrayRef]| -> Result<array::ArrayRef, ExecutionError> {
             // get GIL
             let gil = pyo3::Python::acquire_gil();
             let py = gil.python();
 
+            let to_execution_error = |error: PyErr| {
+                ExecutionError::General(format!("The UDF \"{}\" failed: {:?}", name, error))
+            };
+
             // 1. cast args to Pyarrow arrays
             // 2. call function
             // 3. cast to arrow::array::Array
@@ -26,22 +39,96 @@ pub fn array_udf(func: PyObject) -> ScalarFunctionImplementation {
             // 1.
             let py_args = args
                 .iter()
-                .map(|arg| {
-                    // remove unwrap
-                    to_py_array(arg, py).unwrap()
-                })
-                .collect::<Vec<_>>();
+                .map(|arg| to_py_array(arg, py))
+                .collect::<PyResult<Vec<_>>>()
+                .map_err(to_execution_error)?;
             let py_args = PyTuple::new(py, py_args);
 
             // 2.
-            let value = func.as_ref(py).call(py_args, None);
-            let value = match value {
-                Ok(n) => Ok(n),
-                Err(error) => Err(ExecutionError::General(format!("{:?}", error).to_owned())),
-            }?;
+            let value = func
+                .as_ref(py)
+                .call(py_args, None)
+                .map_err(to_execution_error)?;
 
-            let array = to_rust(value).unwrap();
+            // 3.
+            let array = to_rust(value).map_err(|error| {
+                let type_name = value
+                    .getattr("__class__")
+                    .and_then(|class| class.getattr("__name__"))
+                    .and_then(|name| name.extract::<String>())
+                    .unwrap_or_default();
+                ExecutionError::General(format!(
+                    "The UDF \"{}\" must return a pyarrow.Array, but returned a {} ({:?})",
+                    name, type_name, error
+                ))
+            })?;
+            if array.data_type() != &return_type {
+                return Err(ExecutionError::General(format!(
+                    "The UDF \"{}\" was declared to return {:?}, but returned an array of type {:?}",
+                    name,
+                    return_type,
+                    array.data_type()
+                )));
+            }
+            let rows = args.first().map(|arg| arg.len()).unwrap_or(array.len());
+            if array.len() != rows {
+                return Err(ExecutionError::General(format!(
+                    "The UDF \"{}\" returned {} values for {} rows",
+                    name,
+                    array.len(),
+                    rows
+                )));
+            }
             Ok(array)
         },
     )
 }
+
+/// creates a DataFusion's UDF implementation from a numpy universal function (e.g. `numpy.sqrt`).
+/// Arguments are passed to the ufunc as numpy arrays (nulls become NaN) and the result is
+/// casted to `return_type`.
+pub fn numpy_udf(ufunc: PyObject, return_type: DataType) -> ScalarFunctionImplementation {
+    Arc::new(
+        move |args: &[array::ArrayRef]| -> Result<array::ArrayRef, ExecutionError> {
+            // get GIL
+            let gil = pyo3::Python::acquire_gil();
+            let py = gil.python();
+
+            let to_execution_error = |error: PyErr| ExecutionError::General(format!("{:?}", error));
+
+            // 1. cast args to numpy arrays
+            // 2. call ufunc
+            // 3. cast to arrow::array::Array of `return_type`
+
+            // 1.
+            let kwargs = PyDict::new(py);
+            kwargs
+                .set_item("zero_copy_only", false)
+                .map_err(to_execution_error)?;
+            let py_args = args
+                .iter()
+                .map(|arg| to_py_array(arg, py)?.call_method(py, "to_numpy", (), Some(kwargs)))
+                .collect::<PyResult<Vec<_>>>()
+                .map_err(to_execution_error)?;
+            let py_args = PyTuple::new(py, py_args);
+
+            // 2.
+            let value = ufunc
+                .as_ref(py)
+                .call(py_args, None)
+                .map_err(to_execution_error)?;
+
+            // 3.
+            let kwargs = PyDict::new(py);
+            kwargs
+                .set_item("from_pandas", true)
+                .map_err(to_execution_error)?;
+            let value = py
+                .import("pyarrow")
+                .and_then(|pyarrow| pyarrow.call("array", (value,), Some(kwargs)))
+                .map_err(to_execution_error)?;
+            let array = to_rust(value).map_err(to_execution_error)?;
+            Ok(compute::cast(&array, &return_type)?)
+        },
+    )
+}
@@ -0,0 +1,262 @@
+use arrow::datatypes::DataType;
+use datafusion::logical_plan::{Expr, JoinType, LogicalPlan, Operator};
+use datafusion::scalar::ScalarValue;
+
+use crate::errors::DataFusionError;
+
+type Result<T> = std::result::Result<T, DataFusionError>;
+
+fn not_supported<T>(what: String) -> Result<T> {
+    Err(DataFusionError::Common(format!(
+        "{} can't be converted to SQL",
+        what
+    )))
+}
+
+/// quotes an identifier so that it is not normalized by the SQL parser
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+fn operator_to_sql(op: &Operator) -> Result<&'static str> {
+    Ok(match op {
+        Operator::Eq => "=",
+        Operator::NotEq => "<>",
+        Operator::Lt => "<",
+        Operator::LtEq => "<=",
+        Operator::Gt => ">",
+        Operator::GtEq => ">=",
+        Operator::Plus => "+",
+        Operator::Minus => "-",
+        Operator::Multiply => "*",
+        Operator::Divide => "/",
+        Operator::Modulus => "%",
+        Operator::And => "AND",
+        Operator::Or => "OR",
+        Operator::Like => "LIKE",
+        Operator::NotLike => "NOT LIKE",
+        other => return not_supported(format!("The operator {:?}", other)),
+    })
+}
+
+fn data_type_to_sql(data_type: &DataType) -> Result<&'static str> {
+    Ok(match data_type {
+        DataType::Boolean => "BOOLEAN",
+        DataType::Int8 => "TINYINT",
+        DataType::Int16 => "SMALLINT",
+        DataType::Int32 => "INT",
+        DataType::Int64 => "BIGINT",
+        DataType::Float32 => "FLOAT",
+        DataType::Float64 => "DOUBLE",
+        DataType::Utf8 => "VARCHAR",
+        other => return not_supported(format!("The type {:?}", other)),
+    })
+}
+
+pub(crate) fn scalar_to_sql(value: &ScalarValue) -> Result<String> {
+    Ok(match value {
+        ScalarValue::Boolean(Some(v)) => v.to_string(),
+        ScalarValue::Int8(Some(v)) => v.to_string(),
+        ScalarValue::Int16(Some(v)) => v.to_string(),
+        ScalarValue::Int32(Some(v)) => v.to_string(),
+        ScalarValue::Int64(Some(v)) => v.to_string(),
+        ScalarValue::UInt8(Some(v)) => v.to_string(),
+        ScalarValue::UInt16(Some(v)) => v.to_string(),
+        ScalarValue::UInt32(Some(v)) => v.to_string(),
+        ScalarValue::UInt64(Some(v)) => v.to_string(),
+        // NaN and infinities have no SQL literal
+        ScalarValue::Float32(Some(v)) if v.is_finite() => format!("{:?}", v),
+        ScalarValue::Float64(Some(v)) if v.is_finite() => format!("{:?}", v),
+        ScalarValue::Utf8(Some(v)) | ScalarValue::LargeUtf8(Some(v)) => {
+            format!("'{}'", v.replace('\'', "''"))
+        }
+        ScalarValue::Utf8(None) | ScalarValue::LargeUtf8(None) => "NULL".to_owned(),
+        other => return not_supported(format!("The literal {:?}", other)),
+    })
+}
+
+fn exprs_to_sql(exprs: &[Expr]) -> Result<String> {
+    Ok(exprs
+        .iter()
+        .map(expr_to_sql)
+        .collect::<Result<Vec<_>>>()?
+        .join(", "))
+}
+
+/// converts an expression to SQL
+fn expr_to_sql(expr: &Expr) -> Result<String> {
+    Ok(match expr {
+        Expr::Alias(expr, name) => format!("{} AS {}", expr_to_sql(expr)?, quote(name)),
+        Expr::Column(name) => quote(name),
+        Expr::Literal(value) => scalar_to_sql(value)?,
+        Expr::BinaryExpr { left, op, right } => format!(
+            "({} {} {})",
+            expr_to_sql(left)?,
+            operator_to_sql(op)?,
+            expr_to_sql(right)?
+        ),
+        Expr::Not(expr) => format!("NOT {}", expr_to_sql(expr)?),
+        Expr::IsNull(expr) => format!("{} IS NULL", expr_to_sql(expr)?),
+        Expr::IsNotNull(expr) => format!("{} IS NOT NULL", expr_to_sql(expr)?),
+        Expr::Cast { expr, data_type } => format!(
+            "CAST({} AS {})",
+            expr_to_sql(expr)?,
+            data_type_to_sql(data_type)?
+        ),
+        Expr::Sort {
+            expr,
+            asc,
+            nulls_first,
+        } => format!(
+            "{} {} {}",
+            expr_to_sql(expr)?,
+            if *asc { "ASC" } else { "DESC" },
+            if *nulls_first {
+                "NULLS FIRST"
+            } else {
+                "NULLS LAST"
+            }
+        ),
+        Expr::ScalarFunction { fun, args } => format!("{}({})", fun, exprs_to_sql(args)?),
+        Expr::ScalarUDF { fun, args } => format!("{}({})", fun.name, exprs_to_sql(args)?),
+        Expr::AggregateFunction {
+            fun,
+            args,
+            distinct,
+        } => format!(
+            "{}({}{})",
+            fun,
+            if *distinct { "DISTINCT " } else { "" },
+            exprs_to_sql(args)?
+        ),
+        Expr::AggregateUDF { fun, args } => format!("{}({})", fun.name, exprs_to_sql(args)?),
+        Expr::Wildcard => "*".to_owned(),
+        other => return not_supported(format!("The expression {:?}", other)),
+    })
+}
+
+/// Converts logical plans to SQL. Every node becomes a `SELECT` over its (aliased) input,
+/// so that the result is valid regardless of how the nodes are nested.
+#[derive(Default)]
+struct Unparser {
+    aliases: usize,
+}
+
+impl Unparser {
+    /// returns the SQL of a relation usable in a `FROM` clause
+    fn relation(&mut self, plan: &LogicalPlan) -> Result<String> {
+        match plan {
+            LogicalPlan::TableScan {
+                table_name,
+                projection: None,
+                ..
+            } => Ok(quote(table_name)),
+            _ => {
+                let query = self.query(plan)?;
+                self.aliases += 1;
+                Ok(format!("({}) AS __t{}", query, self.aliases))
+            }
+        }
+    }
+
+    /// returns the SQL query of a plan
+    fn query(&mut self, plan: &LogicalPlan) -> Result<String> {
+        Ok(match plan {
+            LogicalPlan::TableScan {
+                table_name,
+                table_schema,
+                projection,
+                ..
+            } => {
+                let columns = match projection {
+                    Some(projection) => projection
+                        .iter()
+                        .map(|i| quote(table_schema.field(*i).name()))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    None => "*".to_owned(),
+                };
+                format!("SELECT {} FROM {}", columns, quote(table_name))
+            }
+            LogicalPlan::Projection { expr, input, .. } => format!(
+                "SELECT {} FROM {}",
+                exprs_to_sql(expr)?,
+                self.relation(input)?
+            ),
+            LogicalPlan::Filter {
+                predicate, input, ..
+            } => format!(
+                "SELECT * FROM {} WHERE {}",
+                self.relation(input)?,
+                expr_to_sql(predicate)?
+            ),
+            LogicalPlan::Aggregate {
+                input,
+                group_expr,
+                aggr_expr,
+                ..
+            } => {
+                let relation = self.relation(input)?;
+                let select = group_expr
+                    .iter()
+                    .chain(aggr_expr.iter())
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if group_expr.is_empty() {
+                    format!("SELECT {} FROM {}", exprs_to_sql(&select)?, relation)
+                } else {
+                    format!(
+                        "SELECT {} FROM {} GROUP BY {}",
+                        exprs_to_sql(&select)?,
+                        relation,
+                        exprs_to_sql(group_expr)?
+                    )
+                }
+            }
+            LogicalPlan::Sort { expr, input, .. } => format!(
+                "SELECT * FROM {} ORDER BY {}",
+                self.relation(input)?,
+                exprs_to_sql(expr)?
+            ),
+            LogicalPlan::Limit { n, input, .. } => {
+                format!("SELECT * FROM {} LIMIT {}", self.relation(input)?, n)
+            }
+            LogicalPlan::Join {
+                left,
+                right,
+                on,
+                join_type,
+                ..
+            } => {
+                let join = match join_type {
+                    JoinType::Inner => "JOIN",
+                    JoinType::Left => "LEFT JOIN",
+                    JoinType::Right => "RIGHT JOIN",
+                };
+                let on = on
+                    .iter()
+                    .map(|(l, r)| format!("{} = {}", quote(l), quote(r)))
+                    .collect::<Vec<_>>()
+                    .join(" AND ");
+                format!(
+                    "SELECT * FROM {} {} {} ON {}",
+                    self.relation(left)?,
+                    join,
+                    self.relation(right)?,
+                    on
+                )
+            }
+            other => {
+                return not_supported(format!(
+                    "The plan {}",
+                    format!("{:?}", other).lines().next().unwrap_or_default()
+                ))
+            }
+        })
+    }
+}
+
+/// Converts a logical plan to an equivalent SQL query.
+pub(crate) fn plan_to_sql(plan: &LogicalPlan) -> Result<String> {
+    Unparser::default().query(plan)
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use datafusion::scalar::ScalarValue;
+
+use crate::errors::DataFusionError;
+use crate::unparser::scalar_to_sql;
+
+/// replaces each `@name` of `query` by the SQL literal of the variable `name`, if any. Names
+/// within quotes (strings and identifiers) and comments are left as they are.
+pub(crate) fn substitute(
+    query: &str,
+    variables: &HashMap<String, ScalarValue>,
+) -> Result<String, DataFusionError> {
+    if variables.is_empty() {
+        return Ok(query.to_owned());
+    }
+    let is_name = |c: char| c.is_alphanumeric() || c == '_';
+
+    let mut result = String::with_capacity(query.len());
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                result.push(c);
+                // a doubled quote escapes it and is read as two quoted sections
+                for quoted in &mut chars {
+                    result.push(quoted);
+                    if quoted == c {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                result.push(c);
+                for commented in &mut chars {
+                    result.push(commented);
+                    if commented == '\n' {
+                        break;
+                    }
+                }
+            }
+            '@' => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !is_name(c) {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                match variables.get(&name) {
+                    Some(value) => {
+                        let literal = scalar_to_sql(value)?;
+                        // e.g. `a-@b` must not become a comment when `b` is negative
+                        if literal.starts_with('-') {
+                            result.push_str(&format!("({})", literal));
+                        } else {
+                            result.push_str(&literal);
+                        }
+                    }
+                    None => {
+                        result.push('@');
+                        result.push_str(&name);
+                    }
+                }
+            }
+            c => result.push(c),
+        }
+    }
+    Ok(result)
+}
@@ -0,0 +1,122 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use arrow::datatypes::SchemaRef;
+use datafusion::datasource::TableProvider;
+use datafusion::error::Result;
+use datafusion::execution::context::{
+    ExecutionContext as _ExecutionContext, ExecutionContextState,
+};
+use datafusion::logical_plan::{col, Expr, LogicalPlan, LogicalPlanBuilder};
+use datafusion::physical_plan::ExecutionPlan;
+
+/// the name of the table scanned by a view, in the view's own context
+const BASE: &str = "base";
+
+/// A table shared between contexts
+pub(crate) struct SharedTable(pub Arc<dyn TableProvider + Send + Sync>);
+
+impl TableProvider for SharedTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.0.schema()
+    }
+
+    fn scan(
+        &self,
+        projection: &Option<Vec<usize>>,
+        batch_size: usize,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        self.0.scan(projection, batch_size)
+    }
+}
+
+/// The definition of a view: the rows that pass `filter`, with the `columns` expressions
+#[derive(Debug, Clone)]
+pub(crate) struct ViewDefinition {
+    pub filter: Option<Expr>,
+    pub columns: Option<Vec<Expr>>,
+}
+
+impl ViewDefinition {
+    /// creates this view over `base`
+    pub fn apply(&self, base: Arc<dyn TableProvider + Send + Sync>) -> Result<ViewTable> {
+        ViewTable::try_new(base, self.filter.clone(), self.columns.clone())
+    }
+}
+
+/// A table defined by a filter and/or a projection over another table (the base).
+/// Scans of the view execute these on top of the scan of the base, so that the base's rows and
+/// columns are only reachable through them.
+pub(crate) struct ViewTable {
+    state: ExecutionContextState,
+    plan: LogicalPlan,
+    schema: SchemaRef,
+}
+
+impl ViewTable {
+    /// creates a view over `base` with the rows that pass `filter` and the `columns` expressions
+    pub fn try_new(
+        base: Arc<dyn TableProvider + Send + Sync>,
+        filter: Option<Expr>,
+        columns: Option<Vec<Expr>>,
+    ) -> Result<Self> {
+        let mut ctx = _ExecutionContext::new();
+        ctx.register_table(BASE, Box::new(SharedTable(base)));
+
+        let plan = ctx.table(BASE)?.to_logical_plan();
+        let mut builder = LogicalPlanBuilder::from(&plan);
+        if let Some(filter) = filter {
+            builder = builder.filter(filter)?;
+        }
+        if let Some(columns) = columns {
+            builder = builder.project(columns)?;
+        }
+        let plan = builder.build()?;
+        let schema = plan.schema().clone();
+
+        Ok(Self {
+            state: ctx.state,
+            plan,
+            schema,
+        })
+    }
+}
+
+impl TableProvider for ViewTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn scan(
+        &self,
+        projection: &Option<Vec<usize>>,
+        batch_size: usize,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let plan = match projection {
+            Some(projection) => {
+                let columns = projection
+                    .iter()
+                    .map(|i| col(self.schema.field(*i).name()))
+                    .collect();
+                LogicalPlanBuilder::from(&self.plan)
+                    .project(columns)?
+                    .build()?
+            }
+            None => self.plan.clone(),
+        };
+
+        let mut state = self.state.clone();
+        state.config.batch_size = batch_size;
+        let ctx = _ExecutionContext::from(state);
+        let plan = ctx.optimize(&plan)?;
+        ctx.create_physical_plan(&plan)
+    }
+}
@@ -0,0 +1,544 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+use pyo3::{
+    prelude::*,
+    types::{PyBytes, PyDict, PyString},
+};
+
+use arrow::array::UInt32Array;
+use arrow::compute;
+use arrow::csv;
+use arrow::datatypes::{Schema, SchemaRef};
+use arrow::ipc::reader::FileReader as IpcFileReader;
+use arrow::ipc::writer::{FileWriter, StreamWriter};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::writer::InMemoryWriteableCursor;
+
+use crate::errors::DataFusionError;
+
+/// creates `path`, mapping IO errors to a readable message
+fn create(path: &str) -> Result<File, DataFusionError> {
+    File::create(path)
+        .map_err(|e| DataFusionError::Common(format!("Unable to create \"{}\": {}", path, e)))
+}
+
+/// a path next to `path` that is ignored by readers, e.g. `.a.parquet.tmp-Xb3kT0q9Zs`
+fn temporary_path(path: &Path) -> PathBuf {
+    let suffix = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(10)
+        .collect::<String>();
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.tmp-{}", name, suffix))
+}
+
+/// renames `from` to `to`, mapping IO errors to a readable message
+fn rename(from: &Path, to: &Path) -> Result<(), DataFusionError> {
+    fs::rename(from, to).map_err(|e| {
+        DataFusionError::Common(format!(
+            "Unable to move \"{}\" to \"{}\": {}",
+            from.display(),
+            to.display(),
+            e
+        ))
+    })
+}
+
+/// removes the file or directory `path`, if it exists
+fn remove(path: &Path) {
+    // best effort: whatever is left is ignored by readers
+    let _ = fs::remove_file(path).or_else(|_| fs::remove_dir_all(path));
+}
+
+/// the path of `target` when it is one: a `str` or an `os.PathLike` (e.g. a `pathlib.Path`)
+pub(crate) fn to_path(target: &PyAny, py: Python) -> PyResult<Option<String>> {
+    if target.is_instance::<PyString>()? {
+        Ok(Some(target.extract()?))
+    } else if target.hasattr("__fspath__")? {
+        let path = py.import("os")?.call1("fspath", (target,))?;
+        Ok(Some(path.extract()?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// What to do when writing to a path that already exists
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum WriteMode {
+    /// replace it
+    Overwrite,
+    /// add new files to it (a directory)
+    Append,
+    /// fail
+    ErrorIfExists,
+}
+
+impl WriteMode {
+    /// parses "overwrite", "append" or "error_if_exists"
+    pub fn parse(mode: &str) -> Result<Self, DataFusionError> {
+        match mode {
+            "overwrite" => Ok(WriteMode::Overwrite),
+            "append" => Ok(WriteMode::Append),
+            "error_if_exists" => Ok(WriteMode::ErrorIfExists),
+            other => Err(DataFusionError::Common(format!(
+                "The write mode \"{}\" is not one of overwrite, append or error_if_exists",
+                other
+            ))),
+        }
+    }
+
+    /// fails if writing to `path` is not allowed by this mode
+    fn check(&self, path: &Path) -> Result<(), DataFusionError> {
+        if *self == WriteMode::ErrorIfExists && path.exists() {
+            return Err(DataFusionError::Common(format!(
+                "\"{}\" already exists",
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// fails if writing a single file to `path` is not allowed by this mode: files can't be
+    /// appended to
+    pub fn check_file(&self, path: &str) -> Result<(), DataFusionError> {
+        if *self == WriteMode::Append {
+            return Err(DataFusionError::Common(
+                "Only partitioned writes can be appended to".to_owned(),
+            ));
+        }
+        self.check(Path::new(path))
+    }
+}
+
+/// creates `path` (a file or a directory) with `write`, which is given the path to write to.
+/// The result is only moved to `path` once `write` succeeds, so that readers never see a
+/// partially written `path`. An existing `path` is replaced.
+pub(crate) fn write_atomically<T, F>(path: &str, write: F) -> Result<T, DataFusionError>
+where
+    F: FnOnce(&str) -> Result<T, DataFusionError>,
+{
+    let path = Path::new(path);
+    let temporary = temporary_path(path);
+    let result = write(&temporary.to_string_lossy()).and_then(|value| {
+        if path.is_dir() {
+            // directories can't be replaced by a rename: move the old one out of the way first
+            let old = temporary_path(path);
+            rename(path, &old)?;
+            rename(&temporary, path)?;
+            remove(&old);
+        } else {
+            rename(&temporary, path)?;
+        }
+        Ok(value)
+    });
+    if result.is_err() {
+        // the temporary path may not exist, depending on when `write` failed
+        remove(&temporary);
+    }
+    result
+}
+
+/// serializes `batches` in Arrow's IPC streaming format
+pub(crate) fn to_ipc_stream(
+    schema: &Schema,
+    batches: &[RecordBatch],
+) -> Result<Vec<u8>, DataFusionError> {
+    let mut bytes = vec![];
+    {
+        let mut writer = StreamWriter::try_new(&mut bytes, schema)?;
+        for batch in batches {
+            writer.write(batch)?;
+        }
+        writer.finish()?;
+    }
+    Ok(bytes)
+}
+
+/// writes `batches` to `path` in Arrow's IPC file format
+pub(crate) fn write_ipc_file(
+    path: &str,
+    schema: &Schema,
+    batches: &[RecordBatch],
+) -> Result<(), DataFusionError> {
+    let mut writer = FileWriter::try_new(create(path)?, schema)?;
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// serializes `batches` as a parquet file
+pub(crate) fn to_parquet_bytes(
+    schema: SchemaRef,
+    batches: &[RecordBatch],
+) -> Result<Vec<u8>, DataFusionError> {
+    let cursor = InMemoryWriteableCursor::default();
+    let mut writer = ArrowWriter::try_new(cursor.clone(), schema, None)?;
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    writer.close()?;
+    Ok(cursor.data())
+}
+
+/// writes `batches` to `path` as a parquet file
+pub(crate) fn write_parquet_file(
+    path: &str,
+    schema: SchemaRef,
+    batches: &[RecordBatch],
+) -> Result<(), DataFusionError> {
+    let mut writer = ArrowWriter::try_new(create(path)?, schema, None)?;
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+/// writes `batches`, computed one at a time, to `path` as a parquet file, atomically (see
+/// `write_atomically`), returning the number of rows written
+pub(crate) fn write_parquet_stream<I>(
+    path: &str,
+    schema: SchemaRef,
+    batches: I,
+) -> Result<u64, DataFusionError>
+where
+    I: Iterator<Item = Result<RecordBatch, DataFusionError>>,
+{
+    write_atomically(path, |temporary| {
+        let mut writer = ArrowWriter::try_new(create(temporary)?, schema, None)?;
+        let mut rows = 0;
+        for batch in batches {
+            let batch = batch?;
+            writer.write(&batch)?;
+            rows += batch.num_rows() as u64;
+        }
+        writer.close()?;
+        Ok(rows)
+    })
+}
+
+/// writes `batches`, computed one at a time, to `path` as a CSV file with a header, atomically
+/// (see `write_atomically`), returning the number of rows written
+pub(crate) fn write_csv_stream<I>(path: &str, batches: I) -> Result<u64, DataFusionError>
+where
+    I: Iterator<Item = Result<RecordBatch, DataFusionError>>,
+{
+    write_atomically(path, |temporary| {
+        let write_error = |e: io::Error| {
+            DataFusionError::Common(format!("Unable to write \"{}\": {}", temporary, e))
+        };
+        let mut file = BufWriter::new(create(temporary)?);
+        let mut rows = 0;
+        {
+            let mut writer = csv::Writer::new(&mut file);
+            for batch in batches {
+                let batch = batch?;
+                writer.write(&batch)?;
+                rows += batch.num_rows() as u64;
+            }
+        }
+        // flushed explicitly: the errors of the implicit flush on drop would be lost, and the
+        // incomplete file renamed to `path`
+        file.flush().map_err(write_error)?;
+        file.get_ref().sync_all().map_err(write_error)?;
+        Ok(rows)
+    })
+}
+
+/// the number of bytes of a written file hashed at a time
+const HASH_CHUNK_BYTES: usize = 1 << 20;
+
+/// A file written and verified by this crate
+#[derive(Debug, Clone)]
+pub(crate) struct WrittenFile {
+    pub path: String,
+    /// size in bytes
+    pub size: u64,
+    pub rows: u64,
+}
+
+impl WrittenFile {
+    /// converts this file to a Python dictionary, with the SHA-256 of its contents, read a chunk
+    /// at a time
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let read_error = |e: io::Error| {
+            DataFusionError::Common(format!("Unable to read \"{}\": {}", self.path, e))
+        };
+        let mut reader = BufReader::with_capacity(HASH_CHUNK_BYTES, open(&self.path)?);
+        let hasher = py.import("hashlib")?.call_method0("sha256")?;
+        loop {
+            let chunk = reader.fill_buf().map_err(read_error)?;
+            if chunk.is_empty() {
+                break;
+            }
+            hasher.call_method1("update", (PyBytes::new(py, chunk),))?;
+            let length = chunk.len();
+            reader.consume(length);
+        }
+        let sha256 = hasher.call_method0("hexdigest")?;
+
+        let dict = PyDict::new(py);
+        dict.set_item("path", &self.path)?;
+        dict.set_item("size", self.size)?;
+        dict.set_item("rows", self.rows)?;
+        dict.set_item("sha256", sha256)?;
+        Ok(dict.to_object(py))
+    }
+}
+
+/// the file at `path`, failing unless the number of rows read back from it, `read`, is the
+/// number of rows written, `rows`
+fn written_file(path: &str, rows: u64, read: u64) -> Result<WrittenFile, DataFusionError> {
+    if read != rows {
+        return Err(DataFusionError::Common(format!(
+            "\"{}\" has {} rows but {} were written",
+            path, read, rows
+        )));
+    }
+    let size = fs::metadata(path)
+        .map_err(|e| DataFusionError::Common(format!("Unable to read \"{}\": {}", path, e)))?
+        .len();
+    Ok(WrittenFile {
+        path: path.to_owned(),
+        size,
+        rows,
+    })
+}
+
+/// opens `path` to read it back
+fn open(path: &str) -> Result<File, DataFusionError> {
+    File::open(path)
+        .map_err(|e| DataFusionError::Common(format!("Unable to open \"{}\": {}", path, e)))
+}
+
+/// reads back the metadata of the parquet file at `path`, failing unless it has `rows` rows. Only
+/// the footer is read: the data pages are not decoded.
+pub(crate) fn verify_parquet_file(path: &str, rows: u64) -> Result<WrittenFile, DataFusionError> {
+    let reader = SerializedFileReader::new(open(path)?)?;
+    let read = reader.metadata().file_metadata().num_rows() as u64;
+    written_file(path, rows, read)
+}
+
+/// reads back the Arrow IPC file at `path`, failing unless it has `rows` rows
+pub(crate) fn verify_ipc_file(path: &str, rows: u64) -> Result<WrittenFile, DataFusionError> {
+    let mut read = 0;
+    for batch in IpcFileReader::try_new(open(path)?)? {
+        read += batch?.num_rows() as u64;
+    }
+    written_file(path, rows, read)
+}
+
+/// reads back the CSV file (with a header) at `path`, failing unless it has `rows` rows of
+/// `schema`
+pub(crate) fn verify_csv_file(
+    path: &str,
+    schema: SchemaRef,
+    rows: u64,
+) -> Result<WrittenFile, DataFusionError> {
+    let reader = csv::ReaderBuilder::new()
+        .with_schema(schema)
+        .has_header(true)
+        .build(open(path)?)?;
+    let mut read = 0;
+    for batch in reader {
+        read += batch?.num_rows() as u64;
+    }
+    written_file(path, rows, read)
+}
+
+/// a manifest of written files: a Python dictionary with the `files` (see `WrittenFile`) and the
+/// total number of `rows`
+pub(crate) fn manifest(files: &[WrittenFile], py: Python) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item(
+        "files",
+        files
+            .iter()
+            .map(|file| file.to_dict(py))
+            .collect::<PyResult<Vec<_>>>()?,
+    )?;
+    dict.set_item("rows", files.iter().map(|file| file.rows).sum::<u64>())?;
+    Ok(dict.to_object(py))
+}
+
+/// writes `batches` to `path` as a parquet file, atomically (see `write_atomically`).
+/// Returns the path and number of rows of the file.
+pub(crate) fn write_parquet_path(
+    path: &str,
+    schema: SchemaRef,
+    batches: &[RecordBatch],
+    mode: WriteMode,
+) -> Result<Vec<(String, u64)>, DataFusionError> {
+    mode.check_file(path)?;
+    write_atomically(path, |temporary| {
+        write_parquet_file(temporary, schema, batches)
+    })?;
+    let rows = batches.iter().map(|b| b.num_rows() as u64).sum();
+    Ok(vec![(path.to_owned(), rows)])
+}
+
+/// writes each of `partitions` to a file `part-{i}{suffix}.parquet` in `directory`, returning the
+/// name and number of rows of each file. Files are written concurrently, at most
+/// `max_open_files` at a time (all of them when `None`).
+fn write_parquet_partitions(
+    directory: &Path,
+    suffix: &str,
+    schema: SchemaRef,
+    partitions: &[Vec<RecordBatch>],
+    max_open_files: Option<usize>,
+) -> Result<Vec<(String, u64)>, DataFusionError> {
+    fs::create_dir(directory).map_err(|e| {
+        DataFusionError::Common(format!(
+            "Unable to create \"{}\": {}",
+            directory.display(),
+            e
+        ))
+    })?;
+    let max_open_files = max_open_files.unwrap_or(partitions.len()).max(1);
+
+    let mut files = vec![];
+    for (chunk, partitions) in partitions.chunks(max_open_files).enumerate() {
+        let writers = partitions
+            .iter()
+            .enumerate()
+            .map(|(i, batches)| {
+                let name = format!("part-{}{}.parquet", chunk * max_open_files + i, suffix);
+                let rows = batches.iter().map(|b| b.num_rows() as u64).sum();
+
+                let file = directory.join(&name).to_string_lossy().into_owned();
+                let schema = schema.clone();
+                let batches = batches.clone();
+                let writer = thread::spawn(move || write_parquet_file(&file, schema, &batches));
+                (name, rows, writer)
+            })
+            .collect::<Vec<_>>();
+
+        // all writers are joined before failing, so that no file is written after returning
+        let mut result = Ok(());
+        for (name, rows, writer) in writers {
+            let written = writer.join().unwrap_or_else(|_| {
+                Err(DataFusionError::Common(format!(
+                    "The writer of \"{}\" panicked",
+                    name
+                )))
+            });
+            if let Err(e) = written {
+                result = result.and(Err(e));
+            }
+            files.push((name, rows));
+        }
+        result?;
+    }
+    Ok(files)
+}
+
+/// returns `length` rows of `batch` starting at `offset`.
+/// Unlike slicing, this creates arrays without offsets, which the writers expect.
+pub(crate) fn take_rows(
+    schema: SchemaRef,
+    batch: &RecordBatch,
+    offset: usize,
+    length: usize,
+) -> Result<RecordBatch, DataFusionError> {
+    let indices = UInt32Array::from((offset as u32..(offset + length) as u32).collect::<Vec<_>>());
+    let columns = batch
+        .columns()
+        .iter()
+        .map(|column| compute::take(column, &indices, None))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+/// redistributes the rows of `partitions` into partitions of `rows_per_file` rows (the last one
+/// with the remaining rows), preserving their order
+pub(crate) fn split_rows(
+    schema: SchemaRef,
+    partitions: &[Vec<RecordBatch>],
+    rows_per_file: usize,
+) -> Result<Vec<Vec<RecordBatch>>, DataFusionError> {
+    let mut files = vec![];
+    let mut file = vec![];
+    let mut rows = 0;
+    for batch in partitions.iter().flatten() {
+        let mut offset = 0;
+        while offset < batch.num_rows() {
+            let length = (rows_per_file - rows).min(batch.num_rows() - offset);
+            file.push(take_rows(schema.clone(), batch, offset, length)?);
+            offset += length;
+            rows += length;
+            if rows == rows_per_file {
+                files.push(std::mem::take(&mut file));
+                rows = 0;
+            }
+        }
+    }
+    if !file.is_empty() {
+        files.push(file);
+    }
+    Ok(files)
+}
+
+/// Writes each of `partitions` to a parquet file of the directory `path`, followed by an empty
+/// `_SUCCESS` file. New directories (and replaced ones) only appear once all files are written;
+/// when appending to an existing directory, the new files are named after this write
+/// (`part-{i}-{id}.parquet`) and are only moved into it once all of them are written.
+/// Files are written concurrently, at most `max_open_files` at a time (all of them when `None`).
+/// Returns the path and number of rows of each new file.
+pub(crate) fn write_parquet_dataset(
+    path: &str,
+    schema: SchemaRef,
+    partitions: &[Vec<RecordBatch>],
+    mode: WriteMode,
+    max_open_files: Option<usize>,
+) -> Result<Vec<(String, u64)>, DataFusionError> {
+    let root = Path::new(path);
+    mode.check(root)?;
+
+    let files = if mode == WriteMode::Append && root.exists() {
+        if !root.is_dir() {
+            return Err(DataFusionError::Common(format!(
+                "\"{}\" is not a directory and can't be appended to",
+                path
+            )));
+        }
+        let id = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(10)
+            .collect::<String>();
+        let staging = temporary_path(&root.join("staging"));
+        let suffix = format!("-{}", id);
+        let files = write_parquet_partitions(&staging, &suffix, schema, partitions, max_open_files)
+            .and_then(|files| {
+                for (name, _) in &files {
+                    rename(&staging.join(name), &root.join(name))?;
+                }
+                Ok(files)
+            });
+        remove(&staging);
+        let files = files?;
+        create(&root.join("_SUCCESS").to_string_lossy())?;
+        files
+    } else {
+        write_atomically(path, |directory| {
+            let directory = Path::new(directory);
+            let files =
+                write_parquet_partitions(directory, "", schema, partitions, max_open_files)?;
+            create(&directory.join("_SUCCESS").to_string_lossy())?;
+            Ok(files)
+        })?
+    };
+    Ok(files
+        .into_iter()
+        .map(|(name, rows)| (root.join(name).to_string_lossy().into_owned(), rows))
+        .collect())
+}
@@ -1,4 +1,5 @@
 import unittest
+import json
 import tempfile
 import datetime
 import os.path
@@ -56,3 +57,18 @@ def write_parquet(path, data):
     table = pyarrow.Table.from_arrays([data], names=['a'])
     pyarrow.parquet.write_table(table, path)
     return path
+
+
+def write_csv(path, rows, header='a,b'):
+    with open(path, 'w') as f:
+        f.write(header + '\n')
+        for row in rows:
+            f.write(','.join(str(v) for v in row) + '\n')
+    return path
+
+
+def write_json(path, records):
+    with open(path, 'w') as f:
+        for record in records:
+            f.write(json.dumps(record) + '\n')
+    return path
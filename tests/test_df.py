@@ -1,6 +1,14 @@
 import unittest
+import hashlib
+import io
+import json
+import os.path
+import pathlib
+import tempfile
 
 import pyarrow
+import pyarrow.ipc
+import pyarrow.parquet
 import datafusion
 f = datafusion.functions
 
@@ -58,6 +66,46 @@ class TestCase(unittest.TestCase):
         self.assertEqual(len(result.column(0)), 1)
         self.assertEqual(len(result.column(1)), 1)
 
+    def test_expressions(self):
+        df = self._prepare()
+
+        result = df.select(
+            f.col("b") % f.lit(2),
+            (f.col("a") + f.lit(0.5)).alias("c"),
+            (f.col("a") == f.lit(2)).alias("d"),
+            f.col("a").cast(pyarrow.float32()).alias("e"),
+            f.col("a").is_null().alias("f"),
+            f.col("a").is_not_null().alias("g"),
+        ).collect()[0]
+
+        self.assertEqual(result.column(0).to_pylist(), [0, 1, 0])
+        self.assertEqual(result.column(1), pyarrow.array([1.5, 2.5, 3.5]))
+        self.assertEqual(result.column(2), pyarrow.array([False, True, False]))
+        self.assertEqual(result.column(3), pyarrow.array([1, 2, 3], pyarrow.float32()))
+        self.assertEqual(result.column(4), pyarrow.array([False, False, False]))
+        self.assertEqual(result.column(5), pyarrow.array([True, True, True]))
+
+    def test_lit(self):
+        df = self._prepare()
+
+        self.assertEqual(df.filter(f.col("a") > f.lit(2 ** 40) - f.lit(2 ** 40)).collect()[0].num_rows, 3)
+        self.assertEqual(df.filter(f.lit(True)).collect()[0].num_rows, 3)
+        result = df.select(f.lit("x").alias("x"), f.lit(pyarrow.scalar(1, pyarrow.int8())).alias("y")).collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array(["x"] * 3))
+        self.assertEqual(result.column(1).type, pyarrow.int8())
+
+        with self.assertRaises(Exception):
+            f.lit(object())
+
+    def test_sort(self):
+        df = self._prepare()
+
+        result = df.sort(f.col("b").sort(ascending=False)).collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([3, 2, 1]))
+
+        result = df.sort(f.col("a")).collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([1, 2, 3]))
+
     def test_udf(self):
         df = self._prepare()
 
@@ -67,3 +115,481 @@ class TestCase(unittest.TestCase):
         df = df.select(udf(f.col("a")))
 
         self.assertEqual(df.collect()[0].column(0), pyarrow.array([False, False, False]))
+
+    def test_udf_callable_object(self):
+        df = self._prepare()
+
+        class GreaterThan:
+            def __init__(self, threshold):
+                self.threshold = threshold
+                self.calls = 0
+
+            def __call__(self, array):
+                self.calls += 1
+                return pyarrow.array([x.as_py() > self.threshold for x in array])
+
+        greater_than = GreaterThan(1)
+        udf = f.udf(greater_than, [pyarrow.int64()], pyarrow.bool_())
+
+        df = df.select(udf(f.col("a")))
+
+        self.assertEqual(df.collect()[0].column(0), pyarrow.array([False, True, True]))
+        # the same instance is used across executions
+        df.collect()
+        self.assertEqual(greater_than.calls, 2)
+
+    def test_udf_coercion(self):
+        df = self._prepare()
+
+        # the int64 column is cast to the declared float64, like arguments of built-in functions
+        udf = f.udf(lambda x: pyarrow.array([v.as_py() / 2 for v in x]), [pyarrow.float64()], pyarrow.float64())
+
+        df = df.select(udf(f.col("a")))
+
+        self.assertEqual(df.collect()[0].column(0), pyarrow.array([0.5, 1.0, 1.5]))
+
+    def test_udf_errors(self):
+        df = self._prepare()
+
+        def check(udf, message):
+            with self.assertRaises(Exception) as context:
+                df.select(udf(f.col("a"))).collect()
+            self.assertIn(message, str(context.exception))
+
+        check(f.udf(lambda x: [1, 2, 3], [pyarrow.int64()], pyarrow.int64()), "must return a pyarrow.Array")
+        check(f.udf(lambda x: x, [pyarrow.int64()], pyarrow.bool_()), "was declared to return Boolean")
+        check(f.udf(lambda x: x[:1], [pyarrow.int64()], pyarrow.int64()), "returned 1 values for 3 rows")
+
+        with self.assertRaises(Exception) as context:
+            f.udf(lambda x: x, [pyarrow.date32()], pyarrow.int64())
+        self.assertIn("date32[day] is not supported", str(context.exception))
+
+    def test_udf_not_callable(self):
+        with self.assertRaises(Exception):
+            f.udf(1, [pyarrow.int64()], pyarrow.bool_())
+
+    def test_join(self):
+        ctx = datafusion.ExecutionContext()
+
+        left = ctx.create_dataframe([[pyarrow.RecordBatch.from_arrays(
+            [pyarrow.array([1, 2, 3]), pyarrow.array([4, 5, 6])],
+            names=["a", "b"],
+        )]])
+        right = ctx.create_dataframe([[pyarrow.RecordBatch.from_arrays(
+            [pyarrow.array([1, 2]), pyarrow.array([8, 5])],
+            names=["d", "c"],
+        )]])
+
+        df = left.join(right, (["a"], ["d"]), how="inner", filter=f.col("b") < f.col("c"))
+        df = df.select(f.col("b"), f.col("c"))
+
+        result = df.collect()[0]
+
+        self.assertEqual(result.column(0), pyarrow.array([4]))
+        self.assertEqual(result.column(1), pyarrow.array([8]))
+
+    def test_cross_join(self):
+        ctx = datafusion.ExecutionContext()
+
+        left = ctx.from_pydict({"a": [1, 2, 3]})
+        right = ctx.from_pydict({"b": [4, 5]})
+
+        with self.assertRaises(Exception):
+            left.join(right, ([], []))
+
+        with self.assertWarns(RuntimeWarning):
+            df = left.join(right, ([], []), cross_join="warn")
+
+        df = left.join(right, ([], []), cross_join="allow")
+        self.assertEqual(sum(batch.num_rows for batch in df.collect()), 6)
+
+        with self.assertRaises(Exception):
+            left.join(right, (["a"], ["b"]), cross_join="maybe")
+
+        df = left.join(right, ([], []), how="cross")
+        self.assertEqual(sum(batch.num_rows for batch in df.collect()), 6)
+        with self.assertRaises(Exception):
+            left.join(right, (["a"], ["b"]), how="cross")
+
+    def test_join_filter_on_outer_join(self):
+        df = self._prepare()
+
+        with self.assertRaises(Exception):
+            df.join(df, (["a"], ["a"]), how="left", filter=f.col("a") > f.lit(1))
+
+    def test_to_sql(self):
+        ctx = datafusion.ExecutionContext()
+        batch = pyarrow.RecordBatch.from_arrays(
+            [pyarrow.array([1, 2, 3]), pyarrow.array([4, 5, 6])],
+            names=["a", "b"],
+        )
+        df = ctx.create_dataframe([[batch]])
+
+        df = df \
+            .filter(f.col("a") > f.lit(1)) \
+            .select((f.col("a") + f.col("b")).alias("c")) \
+            .limit(1)
+
+        sql = df.to_sql()
+
+        self.assertIn("LIMIT 1", sql)
+        self.assertEqual(ctx.sql(sql).collect(), df.collect())
+
+        # NaN and infinities have no SQL literal
+        with self.assertRaises(Exception):
+            df.filter(f.col("c") < f.lit(float("inf"))).to_sql()
+
+    def test_from_pydict(self):
+        ctx = datafusion.ExecutionContext()
+
+        df = ctx.from_pydict({"a": [1, 2, 3], "b": ["x", "y", None]})
+
+        result = df.collect()[0]
+
+        self.assertEqual(result.schema.names, ["a", "b"])
+        self.assertEqual(result.column(0), pyarrow.array([1, 2, 3]))
+        self.assertEqual(result.column(1), pyarrow.array(["x", "y", None]))
+
+    def test_from_pylist(self):
+        ctx = datafusion.ExecutionContext()
+
+        df = ctx.from_pylist([{"a": 1, "b": 4}, {"a": 2}, {"b": 6, "a": 3}])
+
+        result = df.collect()[0]
+
+        self.assertEqual(result.schema.names, ["a", "b"])
+        self.assertEqual(result.column(0), pyarrow.array([1, 2, 3]))
+        self.assertEqual(result.column(1), pyarrow.array([4, None, 6]))
+
+    def test_explain(self):
+        df = self._prepare()
+
+        df = df.filter(f.col("a") > f.lit(2)).select(f.col("b"))
+
+        self.assertIn("Filter", df.explain())
+
+        plan = json.loads(df.explain(format="json"))
+        self.assertEqual(plan["node"], "Projection")
+        self.assertEqual(plan["schema"], [{"name": "b", "type": "Int64", "nullable": True}])
+        self.assertEqual(plan["inputs"][0]["node"], "Filter")
+        self.assertEqual(len(plan["inputs"][0]["inputs"]), 1)
+
+        with self.assertRaises(Exception):
+            df.explain(format="xml")
+
+        verbose = df.explain(verbose=True)
+        self.assertIn("optimized logical plan:", verbose)
+        self.assertIn("physical plan:", verbose)
+        self.assertIn("FilterExec", verbose)
+        with self.assertRaises(Exception):
+            df.explain(format="json", verbose=True)
+
+    def test_optimizer_trace(self):
+        df = self._prepare()
+
+        df = df.select(f.col("a")).filter(f.col("a") > f.lit(2))
+
+        trace = df.optimizer_trace()
+
+        self.assertEqual([rule for rule, _ in trace], ["projection_push_down", "filter_push_down"])
+        # the projection pushes down the single column used into the scan
+        self.assertIn("projection=Some([0])", trace[0][1])
+        # and the filter is pushed below the projection
+        self.assertNotEqual(trace[1][1], trace[0][1])
+        explanation = df.explain(verbose=True)
+        optimized = explanation.split("optimized logical plan:\n")[1].split("\n\nphysical plan:")[0]
+        self.assertEqual(trace[-1][1], optimized)
+
+    def test_optimizer_trace_matches_optimizer(self):
+        # fails when the rules of the trace drift from the ones of DataFusion's optimizer
+        df = self._prepare()
+        dfs = [
+            df.select(f.col("a")).filter(f.col("a") > f.lit(2)),
+            df.filter(f.col("b") > f.lit(4)).sort(f.col("a")).select(f.col("a")),
+            df.join(df.select(f.col("a").alias("c")), (["a"], ["c"])).select(f.col("b")),
+        ]
+        for df in dfs:
+            explanation = df.explain(verbose=True)
+            optimized = explanation.split("optimized logical plan:\n")[1].split("\n\nphysical plan:")[0]
+            self.assertEqual(df.optimizer_trace()[-1][1], optimized)
+
+    def test_collect_converters(self):
+        df = self._prepare()
+
+        batch = df.collect(converters={"a": pyarrow.float64(), "b": "int8"})[0]
+        self.assertEqual(batch.column(0), pyarrow.array([1.0, 2.0, 3.0]))
+        self.assertEqual(batch.column(1), pyarrow.array([4, 5, 6], pyarrow.int8()))
+
+        batch = df.collect(converters={"a": "datetime64[ms]"})[0]
+        self.assertEqual(batch.column(0).type, pyarrow.timestamp("ms"))
+        self.assertEqual(batch.column(1), pyarrow.array([4, 5, 6]))
+
+        with self.assertRaises(Exception):
+            df.collect(converters={"c": "int8"})
+
+    def test_collect_with_stats(self):
+        df = self._prepare()
+
+        df = df.filter(f.col("a") > f.lit(1))
+
+        batches, stats = df.collect_with_stats()
+
+        self.assertEqual(batches[0].column(0), pyarrow.array([2, 3]))
+        self.assertEqual(stats["output_rows"], 2)
+        self.assertEqual(stats["output_batches"], len(batches))
+        self.assertGreater(stats["output_bytes"], 0)
+        self.assertFalse(stats["truncated"])
+        self.assertGreaterEqual(stats["execution_seconds"], 0)
+        self.assertGreaterEqual(stats["planning_seconds"], 0)
+
+    def test_partition_row_counts(self):
+        ctx = datafusion.ExecutionContext()
+        batches = [
+            pyarrow.RecordBatch.from_arrays([pyarrow.array(list(range(i)))], names=["a"])
+            for i in range(1, 4)
+        ]
+        df = ctx.create_dataframe([[batch] for batch in batches])
+
+        self.assertEqual(df.partition_row_counts(), [1, 2, 3])
+        self.assertEqual(df.filter(f.col("a") > f.lit(0)).partition_row_counts(), [0, 1, 2])
+
+    def test_approx(self):
+        ctx = datafusion.ExecutionContext()
+        batch = pyarrow.RecordBatch.from_arrays(
+            [pyarrow.array(list(range(10000)))], names=["a"]
+        )
+        df = ctx.create_dataframe([[batch]])
+        self.assertIsNone(df.sampled_fraction)
+
+        sampled = df.approx(0.1)
+        self.assertEqual(sampled.sampled_fraction, 0.1)
+        rows = sum(batch.num_rows for batch in sampled.collect())
+        self.assertGreater(rows, 500)
+        self.assertLess(rows, 1500)
+
+        ctx.register_record_batches("t", [[batch]])
+        result = ctx.sql("SELECT COUNT(a) FROM t", sample=0.1)
+        self.assertEqual(result.sampled_fraction, 0.1)
+        self.assertLess(result.collect()[0].column(0)[0].as_py(), 1500)
+        self.assertAlmostEqual(sampled.approx(0.5).sampled_fraction, 0.05)
+
+        self.assertEqual(sum(batch.num_rows for batch in df.approx(1.0).collect()), 10000)
+
+        # a UDF named "sample" is not shadowed by the sampling
+        ctx.register_udf("sample", lambda x: x, [pyarrow.int64()], pyarrow.int64())
+        result = ctx.sql("SELECT sample(a) AS a FROM t WHERE a < 3", sample=1.0).collect()[0]
+        self.assertEqual(result.column(0).to_pylist(), [0, 1, 2])
+        with self.assertRaises(Exception):
+            df.approx(0)
+
+    def test_to_ipc(self):
+        df = self._prepare()
+
+        table = pyarrow.ipc.open_stream(df.to_ipc()).read_all()
+
+        self.assertEqual(table, pyarrow.Table.from_batches(df.collect()))
+
+    def test_write_ipc(self):
+        df = self._prepare()
+
+        with tempfile.TemporaryDirectory() as directory:
+            path = os.path.join(directory, "a.arrow")
+            df.write_ipc(path)
+
+            table = pyarrow.ipc.open_file(path).read_all()
+
+            manifest = df.write_ipc(path, verify=True)
+            self.assertEqual(manifest["rows"], 3)
+            self.assertEqual(manifest["files"][0]["size"], os.path.getsize(path))
+
+            with self.assertRaises(Exception):
+                df.write_ipc(path, mode="error_if_exists")
+            with self.assertRaises(Exception):
+                df.write_ipc(path, mode="append")
+
+        self.assertEqual(table, pyarrow.Table.from_batches(df.collect()))
+
+    def test_write_parquet(self):
+        df = self._prepare()
+        expected = pyarrow.Table.from_batches(df.collect())
+
+        # to a path
+        with tempfile.TemporaryDirectory() as directory:
+            path = os.path.join(directory, "a.parquet")
+            df.write_parquet(path)
+            self.assertEqual(pyarrow.parquet.read_table(path), expected)
+
+        # to an os.PathLike
+        with tempfile.TemporaryDirectory() as directory:
+            path = pathlib.Path(directory) / "a.parquet"
+            df.write_parquet(path, verify=True)
+            self.assertEqual(pyarrow.parquet.read_table(str(path)), expected)
+
+        # to a file-like object
+        buffer = io.BytesIO()
+        df.write_parquet(buffer)
+        self.assertEqual(pyarrow.parquet.read_table(io.BytesIO(buffer.getvalue())), expected)
+
+        # modes only apply to paths
+        with self.assertRaises(Exception):
+            df.write_parquet(io.BytesIO(), mode="error_if_exists")
+
+        # to bytes
+        data = df.write_parquet()
+        self.assertEqual(pyarrow.parquet.read_table(pyarrow.BufferReader(data)), expected)
+
+    def test_write_parquet_verify(self):
+        df = self._prepare()
+
+        with tempfile.TemporaryDirectory() as directory:
+            path = os.path.join(directory, "a.parquet")
+            manifest = df.write_parquet(path, verify=True)
+
+            with open(path, "rb") as file:
+                contents = file.read()
+            self.assertEqual(manifest, {
+                "rows": 3,
+                "files": [{
+                    "path": path,
+                    "size": len(contents),
+                    "rows": 3,
+                    "sha256": hashlib.sha256(contents).hexdigest(),
+                }],
+            })
+
+        with self.assertRaises(Exception):
+            df.write_parquet(io.BytesIO(), verify=True)
+
+    def test_write_parquet_partitioned(self):
+        ctx = datafusion.ExecutionContext()
+        batches = [
+            pyarrow.RecordBatch.from_arrays([pyarrow.array([1, 2])], names=["a"]),
+            pyarrow.RecordBatch.from_arrays([pyarrow.array([3])], names=["a"]),
+        ]
+        df = ctx.create_dataframe([[batches[0]], [batches[1]]])
+
+        with tempfile.TemporaryDirectory() as directory:
+            path = os.path.join(directory, "a")
+            manifest = df.write_parquet(path, verify=True, partitioned=True)
+
+            self.assertEqual(os.listdir(directory), ["a"])
+            self.assertEqual(
+                sorted(os.listdir(path)), ["_SUCCESS", "part-0.parquet", "part-1.parquet"]
+            )
+            self.assertEqual(manifest["rows"], 3)
+            self.assertEqual([file["rows"] for file in manifest["files"]], [2, 1])
+            self.assertEqual(
+                pyarrow.parquet.read_table(os.path.join(path, "part-1.parquet")),
+                pyarrow.Table.from_batches([batches[1]]),
+            )
+
+            # existing directories are not overwritten
+            with self.assertRaises(Exception):
+                df.write_parquet(path, partitioned=True, mode="error_if_exists")
+            self.assertEqual(os.listdir(directory), ["a"])
+
+        with self.assertRaises(Exception):
+            df.write_parquet(partitioned=True)
+
+    def test_write_parquet_max_open_files(self):
+        ctx = datafusion.ExecutionContext()
+        batches = [
+            pyarrow.RecordBatch.from_arrays([pyarrow.array([i])], names=["a"]) for i in range(5)
+        ]
+        df = ctx.create_dataframe([[batch] for batch in batches])
+
+        with tempfile.TemporaryDirectory() as directory:
+            path = os.path.join(directory, "a")
+            manifest = df.write_parquet(path, verify=True, partitioned=True, max_open_files=2)
+
+            self.assertEqual(
+                sorted(os.listdir(path)), ["_SUCCESS"] + ["part-%d.parquet" % i for i in range(5)]
+            )
+            self.assertEqual([file["rows"] for file in manifest["files"]], [1] * 5)
+            self.assertEqual(
+                pyarrow.parquet.read_table(os.path.join(path, "part-3.parquet")),
+                pyarrow.Table.from_batches([batches[3]]),
+            )
+
+            with self.assertRaises(Exception):
+                df.write_parquet(path, partitioned=True, max_open_files=0)
+
+    def test_write_parquet_rows_per_file(self):
+        ctx = datafusion.ExecutionContext()
+        batches = [
+            pyarrow.RecordBatch.from_arrays([pyarrow.array([1, 2, 3])], names=["a"]),
+            pyarrow.RecordBatch.from_arrays([pyarrow.array([4, 5])], names=["a"]),
+        ]
+        df = ctx.create_dataframe([[batches[0]], [batches[1]]])
+
+        with tempfile.TemporaryDirectory() as directory:
+            path = os.path.join(directory, "a")
+            manifest = df.write_parquet(path, verify=True, partitioned=True, rows_per_file=2)
+
+            self.assertEqual([file["rows"] for file in manifest["files"]], [2, 2, 1])
+            self.assertEqual(
+                sorted(pyarrow.parquet.read_table(path).column("a").to_pylist()), [1, 2, 3, 4, 5]
+            )
+
+            with self.assertRaises(Exception):
+                df.write_parquet(path, partitioned=True, rows_per_file=0)
+            with self.assertRaises(Exception):
+                df.write_parquet(os.path.join(directory, "b.parquet"), rows_per_file=2)
+
+    def test_write_parquet_mode(self):
+        df = self._prepare()
+        expected = pyarrow.Table.from_batches(df.collect())
+
+        with tempfile.TemporaryDirectory() as directory:
+            path = os.path.join(directory, "a")
+            df.write_parquet(path, partitioned=True, mode="append")
+            df.write_parquet(path, partitioned=True, mode="append")
+            self.assertEqual(len([name for name in os.listdir(path) if name.endswith(".parquet")]), 2)
+            self.assertEqual(pyarrow.parquet.read_table(path).num_rows, 6)
+
+            df.write_parquet(path, partitioned=True, mode="overwrite")
+            self.assertEqual(sorted(os.listdir(path)), ["_SUCCESS", "part-0.parquet"])
+            self.assertEqual(os.listdir(directory), ["a"])
+
+            with self.assertRaises(Exception):
+                df.write_parquet(path, partitioned=True, mode="error_if_exists")
+
+            file = os.path.join(directory, "b.parquet")
+            df.write_parquet(file)
+            df.write_parquet(file, mode="overwrite")
+            self.assertEqual(pyarrow.parquet.read_table(file), expected)
+            with self.assertRaises(Exception):
+                df.write_parquet(file, mode="error_if_exists")
+            with self.assertRaises(Exception):
+                df.write_parquet(file, mode="append")
+            with self.assertRaises(Exception):
+                df.write_parquet(file, mode="ignore")
+
+    def test_write_atomically(self):
+        df = self._prepare()
+
+        with tempfile.TemporaryDirectory() as directory:
+            # failed writes leave nothing behind
+            with self.assertRaises(Exception):
+                df.write_parquet(os.path.join(directory, "missing", "a.parquet"))
+            self.assertEqual(os.listdir(directory), [])
+
+            df.write_ipc(os.path.join(directory, "a.arrow"))
+            self.assertEqual(os.listdir(directory), ["a.arrow"])
+
+    def test_compute(self):
+        df = self._prepare()
+        column = df.collect()[0].column(0)
+
+        self.assertEqual(datafusion.compute.sum(column).as_py(), 6)
+        self.assertEqual(datafusion.compute.min(column).as_py(), 1)
+        self.assertEqual(datafusion.compute.max(column).as_py(), 3)
+
+        values = pyarrow.array(["a", "b", "a"])
+        self.assertEqual(sorted(datafusion.compute.unique(values).to_pylist()), ["a", "b"])
+
+        counts = datafusion.compute.value_counts(values)
+        self.assertEqual(
+            sorted((c["values"], c["counts"]) for c in counts.to_pylist()),
+            [("a", 2), ("b", 1)],
+        )
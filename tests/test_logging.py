@@ -0,0 +1,17 @@
+import unittest
+
+import datafusion
+
+
+class TestCase(unittest.TestCase):
+
+    def tearDown(self):
+        datafusion.set_log_level("WARNING")
+
+    def test_set_log_level(self):
+        for level in ["OFF", "ERROR", "WARNING", "INFO", "DEBUG", "TRACE", "debug"]:
+            datafusion.set_log_level(level)
+
+    def test_invalid_log_level(self):
+        with self.assertRaises(Exception):
+            datafusion.set_log_level("VERBOSE")
@@ -3,8 +3,11 @@ import tempfile
 import datetime
 import os.path
 import shutil
+import sqlite3
+import threading
 
 import numpy
+import pandas
 import pyarrow
 import datafusion
 
@@ -37,6 +40,597 @@ class TestCase(unittest.TestCase):
 
         self.assertEqual(ctx.tables(), {"t"})
 
+    def test_table(self):
+        ctx = datafusion.ExecutionContext()
+
+        path = write_parquet(os.path.join(self.test_dir, 'a.parquet'), pyarrow.array([3, 1, 2]))
+        ctx.register_parquet("t", path)
+
+        f = datafusion.functions
+        df = ctx.table("t").filter(f.col("a") > f.lit(1)).sort(f.col("a")).limit(1)
+        self.assertEqual(df.collect()[0].column(0), pyarrow.array([2]))
+
+        with self.assertRaises(Exception):
+            ctx.table("u")
+
+    def test_register_parquet_columns(self):
+        ctx = datafusion.ExecutionContext()
+
+        path = os.path.join(self.test_dir, 'a.parquet')
+        table = pyarrow.Table.from_arrays(
+            [pyarrow.array([1, 2, 3]), pyarrow.array([4, 5, 6]), pyarrow.array([7, 8, 9])],
+            names=['a', 'b', 'c'],
+        )
+        pyarrow.parquet.write_table(table, path)
+        ctx.register_parquet("t", path, columns=["c", "a"])
+
+        result = ctx.sql("SELECT * FROM t").collect()[0]
+        self.assertEqual(result.schema.names, ["c", "a"])
+        self.assertEqual(result.column(0), pyarrow.array([7, 8, 9]))
+
+        result = ctx.sql("SELECT SUM(a) FROM t").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([6]))
+
+        with self.assertRaises(Exception):
+            ctx.sql("SELECT b FROM t")
+
+        with self.assertRaises(Exception):
+            ctx.register_parquet("t1", path, columns=["d"])
+
+    def test_row_filter(self):
+        ctx = datafusion.ExecutionContext()
+        f = datafusion.functions
+
+        path = os.path.join(self.test_dir, 'a.parquet')
+        table = pyarrow.Table.from_arrays(
+            [pyarrow.array([1, 1, 2, 3], pyarrow.int32()), pyarrow.array([1, 2, 3, 4])],
+            names=['tenant_id', 'b'],
+        )
+        pyarrow.parquet.write_table(table, path)
+        ctx.register_parquet("t", path)
+
+        with self.assertRaises(Exception):
+            ctx.set_row_filter("t", f.col("c") == f.lit(1))
+        with self.assertRaises(Exception):
+            ctx.set_row_filter("t1", f.col("tenant_id") == f.lit(1))
+
+        ctx.set_row_filter("t", f.col("tenant_id") < f.lit(3))
+        result = ctx.sql("SELECT SUM(b) FROM t").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([6]))
+
+        # filters accumulate
+        ctx.set_row_filter("t", f.col("tenant_id") == f.lit(1))
+        result = ctx.sql("SELECT SUM(b) FROM t WHERE tenant_id <> 2").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([3]))
+
+        # and survive registering the table again
+        ctx.register_parquet("t", path)
+        result = ctx.sql("SELECT COUNT(b) FROM t").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([2], pyarrow.uint64()))
+
+    def test_table_columns(self):
+        ctx = datafusion.ExecutionContext()
+        f = datafusion.functions
+
+        path = os.path.join(self.test_dir, 'a.parquet')
+        table = pyarrow.Table.from_arrays(
+            [pyarrow.array([1, 2]), pyarrow.array(["a@x.com", "b@y.com"])],
+            names=['id', 'email'],
+        )
+        pyarrow.parquet.write_table(table, path)
+        ctx.register_parquet("t", path)
+
+        mask = f.udf(
+            lambda emails: pyarrow.array(["***" for _ in emails]),
+            [pyarrow.utf8()], pyarrow.utf8(),
+        )
+        with self.assertRaises(Exception):
+            ctx.set_table_columns("t", [f.col("name")])
+
+        ctx.set_table_columns("t", [f.col("id").alias("user_id"), mask(f.col("email")).alias("email")])
+        result = ctx.sql("SELECT user_id, email FROM t").collect()[0]
+        self.assertEqual(result.to_pydict(), {"user_id": [1, 2], "email": ["***", "***"]})
+        with self.assertRaises(Exception):
+            ctx.sql("SELECT id FROM t")
+
+        # filters apply to the visible columns and the views survive registering the table again
+        ctx.set_row_filter("t", f.col("user_id") == f.lit(2))
+        ctx.register_parquet("t", path)
+        result = ctx.sql("SELECT * FROM t").collect()[0]
+        self.assertEqual(result.to_pydict(), {"user_id": [2], "email": ["***"]})
+
+    def test_context_manager(self):
+        path = write_parquet(os.path.join(self.test_dir, 'a.parquet'), data())
+
+        with datafusion.ExecutionContext() as ctx:
+            ctx.register_parquet("t", path)
+            df = ctx.sql("SELECT COUNT(a) FROM t")
+            self.assertEqual(ctx.tables(), {"t"})
+
+        self.assertEqual(ctx.tables(), set())
+        # DataFrames outlive the context
+        self.assertEqual(df.collect()[0].column(0), pyarrow.array([100], pyarrow.uint64()))
+
+    def test_reset(self):
+        ctx = datafusion.ExecutionContext()
+
+        path = write_parquet(os.path.join(self.test_dir, 'a.parquet'), data())
+        ctx.register_parquet("t", path)
+        ctx.register_udf("udf", lambda x: x, [pyarrow.float64()], pyarrow.float64())
+
+        ctx.reset()
+
+        self.assertEqual(ctx.tables(), set())
+        ctx.register_parquet("t", path)
+        with self.assertRaises(Exception):
+            ctx.sql("SELECT udf(a) FROM t")
+
+    def test_explain(self):
+        ctx = datafusion.ExecutionContext()
+        ctx.register_parquet("t", write_parquet(os.path.join(self.test_dir, 'a.parquet'), data()))
+
+        self.assertIn("Filter", ctx.explain("SELECT a FROM t WHERE a > 25"))
+        self.assertIn("ParquetExec", ctx.explain("SELECT a FROM t WHERE a > 25", verbose=True))
+
+    def test_write_results(self):
+        ctx = datafusion.ExecutionContext()
+        ctx.register_parquet("t", write_parquet(os.path.join(self.test_dir, 'a.parquet'), data()))
+
+        path = os.path.join(self.test_dir, 'b.parquet')
+        self.assertEqual(ctx.write_parquet("SELECT a FROM t WHERE a > 25", path), 50)
+        table = pyarrow.parquet.read_table(path)
+        self.assertEqual(table.num_rows, 50)
+        self.assertEqual(table.column_names, ["a"])
+
+        path = os.path.join(self.test_dir, 'b.csv')
+        df = ctx.sql("SELECT a FROM t")
+        self.assertEqual(ctx.write_csv(df, path), 100)
+        ctx.register_csv("u", path)
+        result = ctx.sql("SELECT COUNT(a) FROM u").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([100], pyarrow.uint64()))
+
+    def test_write_results_verify_mode(self):
+        ctx = datafusion.ExecutionContext()
+        ctx.register_parquet("t", write_parquet(os.path.join(self.test_dir, 'a.parquet'), data()))
+
+        for write, name in [(ctx.write_parquet, 'b.parquet'), (ctx.write_csv, 'b.csv')]:
+            path = os.path.join(self.test_dir, name)
+            manifest = write("SELECT a FROM t WHERE a > 25", path, verify=True)
+            self.assertEqual(manifest["rows"], 50)
+            self.assertEqual(manifest["files"][0]["path"], path)
+            self.assertEqual(manifest["files"][0]["size"], os.path.getsize(path))
+
+            with self.assertRaises(Exception):
+                write("SELECT a FROM t", path, mode="error_if_exists")
+            with self.assertRaises(Exception):
+                write("SELECT a FROM t", path, mode="append")
+            self.assertEqual(write("SELECT a FROM t", path, mode="overwrite"), 100)
+
+    def test_schema(self):
+        ctx = datafusion.ExecutionContext()
+        batch = pyarrow.RecordBatch.from_arrays(
+            [pyarrow.array([1, 2]), pyarrow.array(["x", None])],
+            names=["a", "b"],
+        )
+        ctx.register_record_batches("t", [[batch]])
+
+        self.assertEqual(ctx.schema("t"), [
+            {"name": "a", "type": "Int64", "nullable": True},
+            {"name": "b", "type": "Utf8", "nullable": True},
+        ])
+        self.assertEqual(ctx.describe("t"), "a: Int64\nb: Utf8")
+
+        with self.assertRaises(KeyError):
+            ctx.schema("u")
+
+    def test_register_temp_table(self):
+        ctx = datafusion.ExecutionContext()
+
+        path = write_parquet(os.path.join(self.test_dir, 'a.parquet'), data())
+        ctx.register_parquet("t", path)
+
+        ctx.register_temp_table("positive", ctx.sql("SELECT a FROM t WHERE a > 0"))
+        batch = pyarrow.RecordBatch.from_arrays([pyarrow.array([1, 2])], names=["b"])
+        ctx.register_temp_table("scratch", [batch])
+
+        self.assertEqual(ctx.tables(), {"t", "positive", "scratch"})
+        self.assertEqual(ctx.temp_tables(), {"positive", "scratch"})
+        result = ctx.sql("SELECT SUM(b) FROM scratch").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([3]))
+
+        # registering a table under the same name makes it permanent
+        ctx.register_record_batches("scratch", [[batch]])
+        self.assertEqual(ctx.temp_tables(), {"positive"})
+
+        ctx.reset()
+        self.assertEqual(ctx.temp_tables(), set())
+
+    def test_snapshot(self):
+        ctx = datafusion.ExecutionContext()
+
+        path = write_parquet(os.path.join(self.test_dir, 'a.parquet'), data())
+        ctx.register_parquet("t", path)
+
+        snapshot = ctx.snapshot()
+        ctx.register_parquet("t1", path)
+        self.assertEqual(snapshot.tables(), {"t"})
+
+        # can be used from other threads
+        results = []
+        thread = threading.Thread(
+            target=lambda: results.append(snapshot.sql("SELECT COUNT(a) FROM t").collect())
+        )
+        thread.start()
+        thread.join()
+        self.assertEqual(results[0][0].column(0), pyarrow.array([100], pyarrow.uint64()))
+
+        with self.assertRaises(Exception):
+            snapshot.sql(f"CREATE EXTERNAL TABLE t2 STORED AS PARQUET LOCATION '{path}'")
+
+    def test_runtime_threads(self):
+        ctx = datafusion.ExecutionContext(worker_threads=1, max_threads=2)
+
+        path = write_parquet(os.path.join(self.test_dir, 'a.parquet'), data())
+        ctx.register_parquet("t", path)
+
+        result = ctx.sql("SELECT COUNT(a) FROM t").collect()
+        self.assertEqual(result[0].column(0), pyarrow.array([100], pyarrow.uint64()))
+
+        with self.assertRaises(Exception):
+            datafusion.ExecutionContext(worker_threads=4, max_threads=2)
+
+    def test_target_batch_bytes(self):
+        path = write_parquet(os.path.join(self.test_dir, 'a.parquet'), data())
+
+        # 100 rows of 8 bytes
+        ctx = datafusion.ExecutionContext(target_batch_bytes=8 * 10)
+        ctx.register_parquet("t", path)
+        result = ctx.sql("SELECT a FROM t").collect()
+        self.assertEqual(len(result), 10)
+        self.assertEqual(sum(batch.num_rows for batch in result), 100)
+
+        with self.assertRaises(Exception):
+            datafusion.ExecutionContext(target_batch_bytes=0)
+
+    def test_set_variable(self):
+        ctx = datafusion.ExecutionContext()
+        batch = pyarrow.RecordBatch.from_arrays(
+            [pyarrow.array([1, 2, 3]), pyarrow.array(["2020-12-31", "2021-01-01", "it's"])],
+            names=["a", "b"],
+        )
+        ctx.register_record_batches("t", [[batch]])
+
+        ctx.set_variable("cutoff", 1)
+        ctx.set_variable("@day", datetime.date(2021, 1, 1).isoformat())
+        ctx.set_variable("name", "it's")
+
+        result = ctx.sql("SELECT a FROM t WHERE a > @cutoff AND b <> '@cutoff'").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([2, 3]))
+
+        result = ctx.sql("SELECT a FROM t WHERE b >= @day").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([2, 3]))
+
+        result = ctx.snapshot().sql("SELECT a FROM t WHERE b = @name").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([3]))
+
+        ctx.set_variable("cutoff", -1)
+        result = ctx.sql("SELECT a-@cutoff AS a FROM t WHERE a = 1").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([2]))
+
+        # values without a SQL literal are rejected
+        for value in [float("nan"), float("inf"), datetime.date(2021, 1, 1)]:
+            with self.assertRaises(Exception):
+                ctx.set_variable("invalid", value)
+
+    def test_exceptions(self):
+        ctx = datafusion.ExecutionContext()
+        ctx.register_parquet("t", write_parquet(os.path.join(self.test_dir, 'a.parquet'), data()))
+
+        self.assertTrue(issubclass(datafusion.PlanError, datafusion.DataFusionError))
+        with self.assertRaises(datafusion.PlanError):
+            ctx.sql("SELECT b FROM t")
+
+        with self.assertRaises(datafusion.TableNotFoundError):
+            ctx.table("u")
+        with self.assertRaises(KeyError):
+            f = datafusion.functions
+            ctx.set_row_filter("u", f.col("a") > f.lit(0))
+
+        path = os.path.join(self.test_dir, 'b.parquet')
+        with open(path, 'w') as file:
+            file.write("not parquet")
+        with self.assertRaises(datafusion.ParquetError):
+            ctx.register_parquet("u", path)
+        self.assertTrue(issubclass(datafusion.ParquetError, IOError))
+
+    def test_suggestions(self):
+        ctx = datafusion.ExecutionContext()
+        ctx.register_parquet("t", write_parquet(os.path.join(self.test_dir, 'a.parquet'), data()))
+
+        with self.assertRaisesRegex(Exception, 'Did you mean "a"'):
+            ctx.sql("SELECT aa FROM t")
+
+        with self.assertRaisesRegex(Exception, 'Did you mean "t"'):
+            ctx.sql("SELECT a FROM tt")
+
+        with self.assertRaisesRegex(Exception, 'Did you mean "a"'):
+            ctx.snapshot().sql("SELECT aa FROM t")
+
+    def test_sql_iter(self):
+        path = write_parquet(os.path.join(self.test_dir, 'a.parquet'), data())
+
+        ctx = datafusion.ExecutionContext(max_concurrent_queries=1, queue_timeout=1)
+        ctx.register_parquet("t", path)
+
+        batches = ctx.sql_iter("SELECT a FROM t", batch_size=10)
+        first = next(batches)
+        self.assertEqual(first.num_rows, 10)
+        rest = list(batches)
+        self.assertEqual(len(rest), 9)
+        self.assertEqual(sum(batch.num_rows for batch in rest), 90)
+
+        # the exhausted iterator no longer holds the only slot of the context
+        result = ctx.sql("SELECT COUNT(a) FROM t").collect()
+        self.assertEqual(result[0].column(0), pyarrow.array([100], pyarrow.uint64()))
+
+        with self.assertRaises(Exception):
+            ctx.sql_iter("SELECT a FROM t", batch_size=0)
+
+    def test_sql_iter_dropped(self):
+        path = write_parquet(os.path.join(self.test_dir, 'a.parquet'), data())
+
+        ctx = datafusion.ExecutionContext()
+        ctx.register_parquet("t", path)
+
+        batches = ctx.sql_iter("SELECT a FROM t", batch_size=10)
+        next(batches)
+        del batches
+
+        # the iterator dropped before being exhausted is a cancelled execution
+        metrics = ctx.metrics_prometheus()
+        self.assertIn("datafusion_queries_total 1\n", metrics)
+        self.assertIn("datafusion_cancelled_queries_total 1\n", metrics)
+        self.assertIn("datafusion_failed_queries_total 0\n", metrics)
+        self.assertIn("datafusion_output_rows_total 10\n", metrics)
+
+    def test_execution_config(self):
+        path = write_parquet(os.path.join(self.test_dir, 'a.parquet'), data())
+
+        config = datafusion.ExecutionConfig(concurrency=2, batch_size=10)
+        self.assertEqual((config.concurrency, config.batch_size), (2, 10))
+
+        ctx = datafusion.ExecutionContext(config=config)
+        ctx.register_parquet("t", path)
+        result = ctx.sql("SELECT a FROM t").collect()
+        self.assertEqual(len(result), 10)
+
+        with self.assertRaises(Exception):
+            datafusion.ExecutionConfig(batch_size=0)
+
+    def test_ordered_aggregates(self):
+        config = datafusion.ExecutionConfig(concurrency=4, ordered_aggregates=True)
+        self.assertTrue(config.ordered_aggregates)
+
+        ctx = datafusion.ExecutionContext(config=config)
+        batches = [
+            pyarrow.RecordBatch.from_arrays(
+                [pyarrow.array([b % 7, b % 3])], names=["b"]
+            )
+            for b in range(20)
+        ]
+        ctx.register_record_batches("t", [batches])
+
+        result = ctx.sql("SELECT b, COUNT(b) FROM t GROUP BY b").collect()
+        result = pyarrow.Table.from_batches(result).to_pydict()
+        self.assertEqual(list(result["b"]), sorted(result["b"]))
+
+        result = ctx.sql("SELECT b FROM t GROUP BY b ORDER BY b DESC LIMIT 2").collect()
+        self.assertEqual(result[0].column(0).to_pylist(), [6, 5])
+
+    def test_max_concurrent_queries(self):
+        ctx = datafusion.ExecutionContext(max_concurrent_queries=1, queue_timeout=10)
+
+        path = write_parquet(os.path.join(self.test_dir, 'a.parquet'), data())
+        ctx.register_parquet("t", path)
+        snapshot = ctx.snapshot()
+
+        results = []
+        threads = [
+            threading.Thread(
+                target=lambda: results.append(snapshot.sql("SELECT COUNT(a) FROM t").collect())
+            )
+            for _ in range(4)
+        ]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+        self.assertEqual(len(results), 4)
+        for result in results:
+            self.assertEqual(result[0].column(0), pyarrow.array([100], pyarrow.uint64()))
+
+        with self.assertRaises(Exception):
+            datafusion.ExecutionContext(max_concurrent_queries=0)
+        with self.assertRaises(Exception):
+            datafusion.ExecutionContext(max_concurrent_queries=1, queue_timeout=-1)
+
+    def test_metrics_prometheus(self):
+        ctx = datafusion.ExecutionContext()
+
+        path = write_parquet(os.path.join(self.test_dir, 'a.parquet'), data())
+        ctx.register_parquet("t", path)
+
+        ctx.sql("SELECT a FROM t").collect()
+        ctx.sql("SELECT COUNT(a) FROM t").collect()
+
+        metrics = ctx.metrics_prometheus()
+
+        self.assertIn("# TYPE datafusion_queries_total counter\ndatafusion_queries_total 2\n", metrics)
+        self.assertIn("datafusion_output_rows_total 101\n", metrics)
+
+    def test_refresh_table(self):
+        ctx = datafusion.ExecutionContext()
+
+        path = write_parquet(os.path.join(self.test_dir, 'a.parquet'), pyarrow.array([1, 2]))
+        ctx.register_parquet("t", path)
+
+        # the file changes schema
+        table = pyarrow.Table.from_arrays([pyarrow.array([3]), pyarrow.array(["x"])], names=['a', 'b'])
+        pyarrow.parquet.write_table(table, path)
+        with self.assertRaises(Exception):
+            ctx.sql("SELECT b FROM t")
+
+        ctx.refresh_table("t")
+        result = ctx.sql("SELECT b FROM t").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array(["x"]))
+
+        ctx.register_dbapi("d", sqlite3.connect(":memory:"), "SELECT 1 AS a")
+        with self.assertRaises(Exception):
+            ctx.refresh_table("d")
+
+    def test_register_csv(self):
+        ctx = datafusion.ExecutionContext()
+
+        path = write_csv(os.path.join(self.test_dir, 'a.csv'), [(1, 1.5), (2, 2.5)])
+        ctx.register_csv("t", path)
+        self.assertEqual(ctx.tables(), {"t"})
+
+        result = ctx.sql("SELECT b FROM t WHERE a > 1").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([2.5]))
+
+        path = os.path.join(self.test_dir, 'b.csv')
+        with open(path, 'w') as f:
+            f.write('1,2\n')
+        ctx.register_csv("u", path, schema=[("x", pyarrow.int32()), ("y", pyarrow.int32())], has_header=False)
+        result = ctx.sql("SELECT y FROM u").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([2], pyarrow.int32()))
+
+        path = os.path.join(self.test_dir, 'c.csv')
+        with open(path, 'w') as f:
+            f.write('a;b\n1;2\n')
+        ctx.register_csv("v", path, delimiter=";")
+        result = ctx.sql("SELECT b FROM v").collect()[0]
+        self.assertEqual(result.column(0).to_pylist(), [2])
+
+        with self.assertRaises(Exception):
+            ctx.register_csv("w", path, delimiter=";;")
+
+    def test_register_json(self):
+        ctx = datafusion.ExecutionContext()
+
+        path = os.path.join(self.test_dir, 'a.json')
+        with open(path, 'w') as f:
+            f.write('{"a": 1, "b": "x"}\n{"a": 2, "b": "y"}\n')
+        ctx.register_json("t", path)
+
+        result = ctx.sql("SELECT b FROM t WHERE a > 1").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array(["y"]))
+
+        with open(path, 'a') as f:
+            f.write('{"a": 3, "b": "z"}\n')
+        ctx.refresh_table("t")
+        result = ctx.sql("SELECT b FROM t WHERE a > 1").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array(["y", "z"]))
+
+        ctx.register_json("u", path, schema=[("a", pyarrow.int32())])
+        result = ctx.sql("SELECT SUM(a) FROM u").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([6], pyarrow.int64()))
+
+    def test_register_record_batches(self):
+        ctx = datafusion.ExecutionContext()
+
+        batches = [
+            pyarrow.RecordBatch.from_arrays([pyarrow.array([1, 2])], names=["a"]),
+            pyarrow.RecordBatch.from_arrays([pyarrow.array([3])], names=["a"]),
+        ]
+        ctx.register_record_batches("t", [[batches[0]], [batches[1]]])
+        self.assertEqual(ctx.tables(), {"t"})
+
+        result = ctx.sql("SELECT SUM(a) FROM t").collect()[0]
+        self.assertEqual(result.column(0).to_pylist(), [6])
+
+        with self.assertRaises(Exception):
+            ctx.register_record_batches("u", [[]])
+
+    def test_register_arrow(self):
+        ctx = datafusion.ExecutionContext()
+
+        table = pyarrow.Table.from_pydict({"a": [1, 2, 3], "b": ["x", "y", "z"]})
+        ctx.register_arrow("t", table)
+
+        result = ctx.sql("SELECT b FROM t WHERE a > 1").to_arrow()
+        self.assertEqual(result, pyarrow.Table.from_pydict({"b": ["y", "z"]}))
+
+        result = ctx.sql("SELECT a, b FROM t WHERE a > 3").to_arrow()
+        self.assertEqual(result.num_rows, 0)
+        self.assertEqual(result.schema.names, ["a", "b"])
+
+    def test_register_pandas(self):
+        ctx = datafusion.ExecutionContext()
+
+        df = pandas.DataFrame({"a": [1, 2, 3], "b": ["x", "y", "z"]}, index=[10, 20, 30])
+        ctx.register_pandas("t", df)
+
+        result = ctx.sql("SELECT a, b FROM t WHERE a > 1").to_pandas()
+        expected = pandas.DataFrame({"a": [2, 3], "b": ["y", "z"]})
+        pandas.testing.assert_frame_equal(result, expected)
+
+    def test_register_numpy(self):
+        ctx = datafusion.ExecutionContext()
+
+        ctx.register_numpy("t", {"a": numpy.array([1.5, 2.5]), "b": numpy.array([1, 2])})
+
+        result = ctx.sql("SELECT a FROM t WHERE b > 1").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([2.5]))
+
+    def test_register_dictionary(self):
+        ctx = datafusion.ExecutionContext()
+
+        table = pyarrow.Table.from_pydict({"a": pyarrow.array(["x", "y", "x"]).dictionary_encode()})
+        ctx.register_arrow("t", table)
+
+        result = ctx.sql("SELECT a, COUNT(a) FROM t GROUP BY a").collect()[0]
+        result = dict(zip(result.column(0).to_pylist(), result.column(1).to_pylist()))
+        self.assertEqual(result, {"x": 2, "y": 1})
+
+        values = pyarrow.array(["x", None]).dictionary_encode()
+        result = ctx.sql("SELECT value FROM v", tables={"v": values}).collect()[0]
+        self.assertEqual(result.column(0).to_pylist(), ["x", None])
+
+    def test_sql_tables_error(self):
+        ctx = datafusion.ExecutionContext()
+
+        # the second table can't be converted: neither table is left registered
+        with self.assertRaises(Exception):
+            ctx.sql("SELECT value FROM a", tables={"a": [1, 2], "b": [object()]})
+        self.assertEqual(ctx.tables(), set())
+
+        result = ctx.sql("SELECT value FROM a", tables={"a": [1, 2]}).collect()[0]
+        self.assertEqual(result.column(0).to_pylist(), [1, 2])
+
+    def test_register_directory(self):
+        ctx = datafusion.ExecutionContext()
+
+        os.makedirs(os.path.join(self.test_dir, 'sales'))
+        os.makedirs(os.path.join(self.test_dir, 'raw', 'events'))
+        write_parquet(os.path.join(self.test_dir, 'sales', 'part-0.parquet'), pyarrow.array([1, 2]))
+        write_parquet(os.path.join(self.test_dir, 'sales', 'part-1.parquet'), pyarrow.array([3]))
+        open(os.path.join(self.test_dir, 'sales', '_SUCCESS'), 'w').close()
+        write_csv(os.path.join(self.test_dir, 'raw', 'events', '2020.csv'), [(1, 2)])
+        write_csv(os.path.join(self.test_dir, 'raw', 'events', '2021.csv'), [(3, 4)])
+        write_parquet(os.path.join(self.test_dir, 'raw', 'users.parquet'), pyarrow.array([1]))
+        open(os.path.join(self.test_dir, 'README'), 'w').close()
+
+        names = ctx.register_directory(self.test_dir)
+
+        self.assertEqual(set(names), {"sales", "raw_events", "raw_users"})
+        self.assertEqual(ctx.tables(), {"sales", "raw_events", "raw_users"})
+
+        result = ctx.sql("SELECT COUNT(a) FROM sales").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([3], pyarrow.uint64()))
+        result = ctx.sql("SELECT COUNT(b) FROM raw_events").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([2], pyarrow.uint64()))
+
     def test_execute(self):
         ctx = datafusion.ExecutionContext()
 
@@ -71,6 +665,268 @@ class TestCase(unittest.TestCase):
         expected = [pyarrow.RecordBatch.from_arrays([expected_a, expected_cast], ['a', 'CAST(a as Int32)'])]
         numpy.testing.assert_equal(expected[0].column(1), expected[0].column(1))
 
+    def test_collect_all_batches(self):
+        ctx = datafusion.ExecutionContext()
+
+        # one batch per file
+        os.makedirs(os.path.join(self.test_dir, 'sales'))
+        write_parquet(os.path.join(self.test_dir, 'sales', 'part-0.parquet'), pyarrow.array([1, 2]))
+        write_parquet(os.path.join(self.test_dir, 'sales', 'part-1.parquet'), pyarrow.array([3]))
+        ctx.register_directory(self.test_dir)
+
+        result = ctx.sql("SELECT a FROM sales").collect()
+        self.assertEqual(len(result), 2)
+        self.assertEqual(sorted(pyarrow.Table.from_batches(result).column(0).to_pylist()), [1, 2, 3])
+
+        # empty results are empty lists of batches
+        result = ctx.sql("SELECT a FROM sales WHERE a > 3").collect()
+        self.assertEqual(sum(batch.num_rows for batch in result), 0)
+
+    def test_max_rows(self):
+        ctx = datafusion.ExecutionContext()
+        values = list(range(10))
+
+        df = ctx.sql("SELECT value FROM t", tables={"t": values}, max_rows=4)
+        batches, stats = df.collect_with_stats()
+        self.assertEqual(sum(batch.num_rows for batch in batches), 4)
+        self.assertTrue(stats["truncated"])
+
+        # the limit applies to DataFrames created from it
+        result = df.select(datafusion.functions.col("value")).collect()
+        self.assertEqual(sum(batch.num_rows for batch in result), 4)
+
+        # queries with fewer rows (e.g. with a smaller LIMIT) are not truncated
+        df = ctx.sql("SELECT value FROM t LIMIT 2", tables={"t": values}, max_rows=4)
+        batches, stats = df.collect_with_stats()
+        self.assertEqual(sum(batch.num_rows for batch in batches), 2)
+        self.assertFalse(stats["truncated"])
+
+        df = ctx.sql("SELECT value FROM t", tables={"t": values}, max_rows=10)
+        self.assertFalse(df.collect_with_stats()[1]["truncated"])
+
+        ctx.register_record_batches("u", [[pyarrow.RecordBatch.from_arrays([pyarrow.array(values)], names=["a"])]])
+        df = ctx.snapshot().sql("SELECT a FROM u", max_rows=0)
+        self.assertEqual(sum(batch.num_rows for batch in df.collect()), 0)
+
+    def test_sql_policy(self):
+        ctx = datafusion.ExecutionContext()
+        batch = pyarrow.RecordBatch.from_arrays([pyarrow.array([1.0, 4.0])], names=["a"])
+        ctx.register_record_batches("t", [[batch]])
+        ctx.register_record_batches("secrets", [[batch]])
+
+        policy = datafusion.SqlPolicy(allowed_tables={"t"}, denied_functions={"SQRT"})
+
+        result = ctx.sql("SELECT SUM(a) FROM t", policy=policy).collect()[0]
+        self.assertEqual(result.column(0).to_pylist(), [5.0])
+
+        with self.assertRaises(PermissionError):
+            ctx.sql("SELECT a FROM secrets", policy=policy)
+        with self.assertRaises(PermissionError):
+            ctx.sql("SELECT t.a FROM t JOIN secrets ON t.a = secrets.a", policy=policy)
+        with self.assertRaises(PermissionError):
+            ctx.sql("SELECT sqrt(a) FROM t", policy=policy)
+        with self.assertRaises(PermissionError):
+            ctx.snapshot().sql("SELECT a FROM secrets", policy=policy)
+
+        # tables given to the statement are always allowed
+        result = ctx.sql("SELECT value FROM v", tables={"v": [1, 2]}, policy=policy).collect()[0]
+        self.assertEqual(result.column(0).to_pylist(), [1, 2])
+
+        # statements can't register tables
+        path = write_parquet(os.path.join(self.test_dir, 'a.parquet'), pyarrow.array([1]))
+        with self.assertRaises(PermissionError):
+            ctx.sql(f"CREATE EXTERNAL TABLE u STORED AS PARQUET LOCATION '{path}'", policy=datafusion.SqlPolicy())
+        self.assertEqual(ctx.tables(), {"t", "secrets"})
+
+    def test_collect_strings(self):
+        ctx = datafusion.ExecutionContext()
+
+        for data_type in [pyarrow.utf8(), pyarrow.large_utf8()]:
+            values = pyarrow.array(["a", None, "c"], data_type)
+            result = ctx.sql("SELECT value FROM t", tables={"t": values}).collect()[0]
+            self.assertEqual(result.column(0).type, data_type)
+            self.assertEqual(result.column(0).to_pylist(), ["a", None, "c"])
+
+    def test_read_parquet(self):
+        ctx = datafusion.ExecutionContext()
+
+        path = write_parquet(os.path.join(self.test_dir, 'a.parquet'), pyarrow.array([1, 2, 3]))
+
+        result = ctx.read_parquet(path).collect()[0]
+
+        self.assertEqual(result.column(0), pyarrow.array([1, 2, 3]))
+
+    def test_read_csv(self):
+        ctx = datafusion.ExecutionContext()
+
+        path = write_csv(os.path.join(self.test_dir, 'a.csv'), [(1, 1.5), (2, 2.5)])
+
+        df = ctx.read_csv(path).select(datafusion.functions.col("b"))
+        self.assertEqual(df.collect()[0].column(0), pyarrow.array([1.5, 2.5]))
+
+        df = ctx.read_csv(path, schema=[("a", pyarrow.int32()), ("b", pyarrow.float32())])
+        self.assertEqual(df.collect()[0].column(0), pyarrow.array([1, 2], pyarrow.int32()))
+
+        path = write_csv(os.path.join(self.test_dir, 'b.csv'), [], header='a|b')
+        with self.assertRaises(Exception):
+            ctx.read_csv(path, delimiter="||")
+
+    def test_read_json(self):
+        ctx = datafusion.ExecutionContext()
+
+        path = write_json(os.path.join(self.test_dir, 'a.json'), [{"a": 1}, {"a": 2, "b": "x"}])
+
+        result = ctx.read_json(path).collect()[0]
+
+        self.assertEqual(result.to_pydict(), {"a": [1, 2], "b": [None, "x"]})
+
+    def test_sql_tables(self):
+        ctx = datafusion.ExecutionContext()
+
+        path = write_parquet(os.path.join(self.test_dir, 'a.parquet'), data())
+        ctx.register_parquet("t", path)
+
+        df = ctx.sql("SELECT value FROM ids WHERE value > 2", tables={"ids": [1, 2, 3, 4]})
+
+        # the table is only available to that statement
+        self.assertEqual(ctx.tables(), {"t"})
+
+        result = df.collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([3, 4]))
+
+        # registered names can't be shadowed
+        with self.assertRaises(Exception):
+            ctx.sql("SELECT value FROM t", tables={"t": [1]})
+
+    def test_register_dbapi(self):
+        connection = sqlite3.connect(":memory:")
+        connection.execute("CREATE TABLE t (a INTEGER, b TEXT)")
+        connection.executemany("INSERT INTO t VALUES (?, ?)", [(1, "x"), (2, None), (3, "z")])
+
+        ctx = datafusion.ExecutionContext()
+        ctx.register_dbapi("t", connection, "SELECT * FROM t")
+
+        result = ctx.sql("SELECT b FROM t WHERE a > 1").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([None, "z"]))
+
+        # rows are fetched on every execution
+        connection.execute("INSERT INTO t VALUES (4, 'w')")
+        result = ctx.sql("SELECT COUNT(a) FROM t").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([4], pyarrow.uint64()))
+
+    def test_register_dbapi_driver_error(self):
+        connection = sqlite3.connect(":memory:")
+        connection.execute("CREATE TABLE t (a INTEGER)")
+
+        ctx = datafusion.ExecutionContext()
+        ctx.register_dbapi("t", connection, "SELECT * FROM t", schema=[("a", pyarrow.int64())])
+        connection.execute("DROP TABLE t")
+
+        # the exception of the driver is raised as it is
+        with self.assertRaises(sqlite3.OperationalError):
+            ctx.sql("SELECT a FROM t").collect()
+
+    def test_register_dbapi_schema(self):
+        connection = sqlite3.connect(":memory:")
+        connection.execute("CREATE TABLE t (a INTEGER)")
+        connection.execute("INSERT INTO t VALUES (NULL)")
+
+        ctx = datafusion.ExecutionContext()
+        # the type of a column of nulls can't be inferred
+        with self.assertRaises(Exception):
+            ctx.register_dbapi("t", connection, "SELECT * FROM t")
+
+        ctx.register_dbapi("t", connection, "SELECT * FROM t", schema=[("a", pyarrow.int32())])
+        result = ctx.sql("SELECT a FROM t").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([None], pyarrow.int32()))
+
+    def test_register_provider(self):
+        class Squares:
+            def __init__(self):
+                self.projections = []
+
+            def schema(self):
+                return pyarrow.schema([("x", pyarrow.int64()), ("square", pyarrow.int64())])
+
+            def scan(self, projection, filters):
+                self.projections.append(projection)
+                xs = list(range(5))
+                return [
+                    pyarrow.RecordBatch.from_arrays(
+                        [pyarrow.array(xs), pyarrow.array([x * x for x in xs])],
+                        names=["x", "square"],
+                    )
+                ]
+
+        provider = Squares()
+        ctx = datafusion.ExecutionContext()
+        ctx.register_provider("squares", provider)
+
+        # the provider ignores the projection: the columns are selected from its batches
+        result = ctx.sql("SELECT square FROM squares WHERE square > 5").collect()[0]
+        self.assertEqual(result.column(0), pyarrow.array([9, 16]))
+        self.assertEqual(provider.projections, [["square"]])
+
+        # explaining the plan doesn't scan the provider
+        explanation = ctx.explain("SELECT square FROM squares", verbose=True)
+        self.assertIn('not planned: planning would scan the tables backed by Python code "squares"', explanation)
+        self.assertEqual(provider.projections, [["square"]])
+
+        class Invalid:
+            def schema(self):
+                return pyarrow.schema([("a", pyarrow.int64())])
+
+            def scan(self, projection, filters):
+                return [pyarrow.RecordBatch.from_arrays([pyarrow.array([1])], names=["b"])]
+
+        ctx.register_provider("invalid", Invalid())
+        with self.assertRaises(Exception):
+            ctx.sql("SELECT a FROM invalid").collect()
+
+        class Failing(Invalid):
+            def scan(self, projection, filters):
+                raise ConnectionError("unreachable")
+
+        ctx.register_provider("failing", Failing())
+        with self.assertRaises(ConnectionError):
+            ctx.sql("SELECT a FROM failing").collect()
+
+        # each scan raises its own exception, even when their messages are the same
+        expected = [ConnectionError("unreachable"), ConnectionError("unreachable")]
+        errors = list(expected)
+
+        class FailingEach(Invalid):
+            def scan(self, projection, filters):
+                raise errors.pop(0)
+
+        ctx.register_provider("failing_each", FailingEach())
+        first = ctx.sql("SELECT a FROM failing_each")
+        second = ctx.sql("SELECT a FROM failing_each")
+        raised = []
+        for df in [first, second]:
+            try:
+                df.collect()
+            except ConnectionError as error:
+                raised.append(error)
+        self.assertEqual(len(raised), 2)
+        self.assertIs(raised[0], expected[0])
+        self.assertIs(raised[1], expected[1])
+
+    def test_column_order(self):
+        ctx = datafusion.ExecutionContext()
+
+        table = pyarrow.Table.from_arrays(
+            [pyarrow.array([1]), pyarrow.array([2]), pyarrow.array([3])], names=['a', 'b', 'c'],
+        )
+        path = os.path.join(self.test_dir, 'a.parquet')
+        pyarrow.parquet.write_table(table, path)
+        ctx.register_parquet("t", path)
+
+        result = ctx.sql("SELECT c, a, b AS z FROM t").collect()[0]
+
+        self.assertEqual(result.schema.names, ["c", "a", "z"])
+        self.assertEqual(result.to_pydict(), {"c": [3], "a": [1], "z": [2]})
+
     def test_cast(self):
         """
         Verify that we can cast
@@ -127,6 +983,20 @@ class TestCase(unittest.TestCase):
             pyarrow.array([False, True, False])
         )
 
+    def test_ufunc(self):
+        ctx = datafusion.ExecutionContext()
+
+        path = write_parquet(os.path.join(self.test_dir, 'a.parquet'), pyarrow.array([1.0, None, 4.0]))
+        ctx.register_parquet("t", path)
+
+        ctx.register_ufunc("sqrt", numpy.sqrt)
+        ctx.register_ufunc("add", numpy.add)
+
+        batches = ctx.sql("SELECT sqrt(a) AS s, add(a, a) AS d FROM t").collect()
+
+        self.assertEqual(batches[0].column(0), pyarrow.array([1.0, None, 2.0]))
+        self.assertEqual(batches[0].column(1), pyarrow.array([2.0, None, 8.0]))
+
 
 class TestIO(unittest.TestCase):
     def setUp(self):
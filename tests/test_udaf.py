@@ -69,3 +69,20 @@ class TestCase(unittest.TestCase):
         result = df.collect()[0]
 
         self.assertEqual(result.column(1), pyarrow.array([1.0 + 2.0, 3.0]))
+
+    def test_register_udaf(self):
+        ctx = datafusion.ExecutionContext()
+        batch = pyarrow.RecordBatch.from_arrays(
+            [pyarrow.array([1.0, 2.0, 3.0]), pyarrow.array([4, 4, 6])],
+            names=["a", "b"],
+        )
+        ctx.register_record_batches("t", [[batch]])
+
+        ctx.register_udaf("my_sum", Accumulator, pyarrow.float64(), pyarrow.float64(), [pyarrow.float64()])
+
+        result = ctx.sql("SELECT b, my_sum(a) FROM t GROUP BY b").collect()[0]
+        result = dict(zip(result.column(0).to_pylist(), result.column(1).to_pylist()))
+        self.assertEqual(result, {4: 1.0 + 2.0, 6: 3.0})
+
+        with self.assertRaises(Exception):
+            ctx.register_udaf("other", 1, pyarrow.float64(), pyarrow.float64(), [pyarrow.float64()])